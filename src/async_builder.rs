@@ -0,0 +1,187 @@
+use crate::{file_pieces, Builder, Header, Section, Symbol, SymbolOptions};
+use std::io::Result;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// An asynchronous counterpart of [`Builder`], for use with Tokio.
+///
+/// This is available only when the `tokio` feature is enabled.
+///
+/// The `add_symbol` family of functions accept [`AsyncRead`] sources
+/// instead of `std::io::Read`, and [`close`](Self::close) writes to an
+/// [`AsyncWrite`], which needn't also implement `AsyncSeek`. The rest of the
+/// `Builder` API, which doesn't need asynchronous I/O, is available through
+/// [`builder_mut`](Self::builder_mut). Because the file is assembled in
+/// exactly the same way, the result is byte-for-byte identical to what
+/// `Builder` would produce.
+///
+/// ```
+/// # async fn example() -> std::io::Result<()> {
+/// # let output_file: Vec<u8> = Vec::new();
+/// let mut builder = elfbin::AsyncBuilder::new(
+///     elfbin::Header {
+///         class: elfbin::Class::ELF64,
+///         encoding: elfbin::Encoding::LSB,
+///         machine: 62, // x86_64
+///         flags: 0,
+///     },
+///     output_file,
+/// )?;
+/// builder.add_symbol("example", &b"hello!"[..]).await?;
+/// builder.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncBuilder<W: AsyncWrite + Unpin> {
+    inner: Builder<Vec<u8>>,
+    w: W,
+}
+
+impl<W> AsyncBuilder<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Begin constructing a new ELF file with the given header information
+    /// in the given writer.
+    ///
+    /// See [`Builder::new`] for details.
+    pub fn new(hdr: Header, target: W) -> Result<Self> {
+        Ok(Self {
+            inner: Builder::new(hdr, Vec::new())?,
+            w: target,
+        })
+    }
+
+    /// Define a new symbol with the given name whose contents are read from
+    /// the given source.
+    ///
+    /// See [`Builder::add_symbol`] for details.
+    pub async fn add_symbol<S: Into<String>, R: AsyncRead + Unpin>(
+        &mut self,
+        name: S,
+        src: R,
+    ) -> Result<Symbol> {
+        self.add_symbol_with(name, SymbolOptions::default(), src)
+            .await
+    }
+
+    /// See [`Builder::add_symbol_align`].
+    pub async fn add_symbol_align<S: Into<String>, R: AsyncRead + Unpin>(
+        &mut self,
+        name: S,
+        alignment: usize,
+        src: R,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        self.add_symbol_with(name, opts, src).await
+    }
+
+    /// See [`Builder::add_symbol_in`].
+    pub async fn add_symbol_in<S: Into<String>, R: AsyncRead + Unpin>(
+        &mut self,
+        section: Section,
+        name: S,
+        src: R,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            section: Some(section),
+            ..SymbolOptions::default()
+        };
+        self.add_symbol_with(name, opts, src).await
+    }
+
+    /// See [`Builder::add_symbol_align_in`].
+    pub async fn add_symbol_align_in<S: Into<String>, R: AsyncRead + Unpin>(
+        &mut self,
+        section: Section,
+        name: S,
+        alignment: usize,
+        src: R,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            section: Some(section),
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        self.add_symbol_with(name, opts, src).await
+    }
+
+    /// See [`Builder::add_symbol_with`].
+    ///
+    /// The data is read directly into the buffer of the section it's placed
+    /// in, as it is by `Builder`. If the returned future is dropped before
+    /// it completes, the data read so far is discarded and the builder is
+    /// left as it was before the call.
+    pub async fn add_symbol_with<S: Into<String>, R: AsyncRead + Unpin>(
+        &mut self,
+        name: S,
+        opts: SymbolOptions,
+        mut src: R,
+    ) -> Result<Symbol> {
+        let name = name.into();
+        let alignment = self.inner.check_data_symbol(&name, &opts)?;
+        let mut pending = self.inner.begin_data_symbol(&name, alignment, &opts)?;
+        let result = src.read_to_end(pending.data()).await.map(|_| ());
+        pending.end(name, &opts, result, true)
+    }
+
+    /// Returns the underlying [`Builder`], such as to generate a
+    /// [C header](Builder::c_header) for the symbols added so far.
+    pub fn builder(&self) -> &Builder<Vec<u8>> {
+        &self.inner
+    }
+
+    /// Returns the underlying [`Builder`], for adding sections and symbols
+    /// whose data needn't be read asynchronously, or changing its options.
+    ///
+    /// The builder writes to an in-memory buffer, which is only copied to
+    /// the real writer by [`close`](Self::close) or
+    /// [`close_verified`](Self::close_verified).
+    ///
+    /// ```
+    /// # async fn example() -> std::io::Result<()> {
+    /// # let mut builder = elfbin::AsyncBuilder::new(
+    /// #     elfbin::Header {
+    /// #         class: elfbin::Class::ELF64,
+    /// #         encoding: elfbin::Encoding::LSB,
+    /// #         machine: 62, // x86_64
+    /// #         flags: 0,
+    /// #     },
+    /// #     Vec::new(),
+    /// # )?;
+    /// let fonts = builder.builder_mut().add_section(".rodata.fonts");
+    /// builder.add_symbol_in(fonts, "font", &b"font"[..]).await?;
+    /// builder.builder_mut().add_string_symbol("greeting", "hello")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder_mut(&mut self) -> &mut Builder<Vec<u8>> {
+        &mut self.inner
+    }
+
+    /// Writes the complete ELF file to the underlying writer and then
+    /// returns that writer.
+    ///
+    /// See [`Builder::close`] for details.
+    pub async fn close(mut self) -> Result<W> {
+        let layout = self.inner.layout()?;
+        for piece in file_pieces(&layout, &self.inner.sections) {
+            self.w.write_all(piece).await?;
+        }
+        self.w.flush().await?;
+        Ok(self.w)
+    }
+
+    /// Like [`close`](Self::close), but first checks the generated file.
+    ///
+    /// See [`Builder::close_verified`] for details.
+    pub async fn close_verified(mut self) -> Result<W> {
+        let buf = self.inner.file_bytes()?;
+        self.inner.verify(&buf)?;
+        self.w.write_all(&buf).await?;
+        self.w.flush().await?;
+        Ok(self.w)
+    }
+}
@@ -0,0 +1,455 @@
+//! The `elfbin diff` subcommand, which compares the symbols of two ELF files.
+
+use elfbin::reader::{SymbolInfo, SymbolSection};
+use elfbin::Reader;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Error, Read, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(
+    about = "Compare the symbols of two ELF files, exiting with status 1 if they differ",
+    after_help = "Each difference is reported on a separate line starting with one of:
+
+    added      The symbol is only in NEW
+    removed    The symbol is only in OLD
+    resized    The symbol's size differs
+    changed    The symbol's contents differ, or for a symbol that isn't in a
+               section, its value
+
+Symbol contents are identified by their SHA-256 hashes. A symbol that moves
+to a different address without changing its contents isn't reported, and
+neither is a change to a symbol's binding or visibility. Symbols that share a
+name are paired up in the order they appear in each file, and are labelled
+with their position among the symbols of that name, such as table[2]."
+)]
+pub struct DiffCommand {
+    #[structopt(name = "OLD", help = "The original ELF file")]
+    pub old: PathBuf,
+
+    #[structopt(name = "NEW", help = "The ELF file to compare with the original")]
+    pub new: PathBuf,
+}
+
+/// Compares the files and returns the exit status for the program, which
+/// is 1 if there are any differences.
+pub fn run(args: DiffCommand) -> Result<i32, Error> {
+    let old_buf = std::fs::read(&args.old)?;
+    let old = Reader::new(&old_buf)?;
+    let new_buf = std::fs::read(&args.new)?;
+    let new = Reader::new(&new_buf)?;
+
+    let differences = compare(&old, &new)?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    for difference in differences.iter() {
+        writeln!(out, "{}", difference)?;
+    }
+    out.flush()?;
+    Ok(if differences.is_empty() { 0 } else { 1 })
+}
+
+/// A difference between two ELF files, as found by [`compare`].
+///
+/// Symbols are identified by a label that is usually just their name. Linked
+/// files often have several local symbols of the same name, and so those are
+/// paired up in the order they appear in each file and labelled with their
+/// position among the symbols of that name, such as `table[2]`.
+#[derive(PartialEq, Debug)]
+enum Difference {
+    Header {
+        field: &'static str,
+        old: String,
+        new: String,
+    },
+    Removed {
+        label: String,
+        content: Content,
+    },
+    Added {
+        label: String,
+        content: Content,
+    },
+    Resized {
+        label: String,
+        old_size: u64,
+        new_size: u64,
+        old: Content,
+        new: Content,
+    },
+    Changed {
+        label: String,
+        old: Content,
+        new: Content,
+    },
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::Header { field, old, new } => {
+                write!(f, "header   {}  {} -> {}", field, old, new)
+            }
+            Difference::Removed { label, content } => write!(f, "removed  {}  {}", label, content),
+            Difference::Added { label, content } => write!(f, "added    {}  {}", label, content),
+            Difference::Resized {
+                label,
+                old_size,
+                new_size,
+                old,
+                new,
+            } => write!(
+                f,
+                "resized  {}  {} -> {} bytes  ({} -> {})",
+                label,
+                old_size,
+                new_size,
+                old.digest(),
+                new.digest(),
+            ),
+            Difference::Changed { label, old, new } => write!(
+                f,
+                "changed  {}  {} -> {}",
+                label,
+                old.digest(),
+                new.digest(),
+            ),
+        }
+    }
+}
+
+/// Returns the differences between the headers and symbols of two files,
+/// with the header differences first, then the symbols that were removed,
+/// resized or changed in the order of the old file, and finally the symbols
+/// that were added in the order of the new file.
+fn compare(old: &Reader, new: &Reader) -> Result<Vec<Difference>, Error> {
+    let mut differences: Vec<Difference> = Vec::new();
+
+    let (old_hdr, new_hdr) = (old.header(), new.header());
+    let header_fields = [
+        (
+            "class",
+            format!("{:?}", old_hdr.class),
+            format!("{:?}", new_hdr.class),
+        ),
+        (
+            "encoding",
+            format!("{:?}", old_hdr.encoding),
+            format!("{:?}", new_hdr.encoding),
+        ),
+        (
+            "machine",
+            format!("{:#x}", old_hdr.machine),
+            format!("{:#x}", new_hdr.machine),
+        ),
+        (
+            "flags",
+            format!("{:#010x}", old_hdr.flags),
+            format!("{:#010x}", new_hdr.flags),
+        ),
+    ];
+    for (field, old_value, new_value) in header_fields.iter() {
+        if old_value != new_value {
+            differences.push(Difference::Header {
+                field,
+                old: old_value.clone(),
+                new: new_value.clone(),
+            });
+        }
+    }
+
+    let old_syms = comparable_symbols(old);
+    let new_syms = comparable_symbols(new);
+    let old_keys = occurrence_keys(&old_syms);
+    let new_keys = occurrence_keys(&new_syms);
+    let new_by_key: BTreeMap<(&str, usize), &SymbolInfo> = new_keys
+        .iter()
+        .copied()
+        .zip(new_syms.iter().copied())
+        .collect();
+    let old_key_set: BTreeSet<(&str, usize)> = old_keys.iter().copied().collect();
+    let label = |(name, occurrence): (&str, usize)| {
+        if old_key_set.contains(&(name, 2)) || new_by_key.contains_key(&(name, 2)) {
+            format!("{}[{}]", name, occurrence)
+        } else {
+            String::from(name)
+        }
+    };
+
+    for (old_sym, &key) in old_syms.iter().zip(old_keys.iter()) {
+        let old_content = content(old, old_sym)?;
+        let new_sym = match new_by_key.get(&key) {
+            Some(sym) => sym,
+            None => {
+                differences.push(Difference::Removed {
+                    label: label(key),
+                    content: old_content,
+                });
+                continue;
+            }
+        };
+        let new_content = content(new, new_sym)?;
+        if old_sym.size != new_sym.size {
+            differences.push(Difference::Resized {
+                label: label(key),
+                old_size: old_sym.size,
+                new_size: new_sym.size,
+                old: old_content,
+                new: new_content,
+            });
+        } else if old_content != new_content {
+            differences.push(Difference::Changed {
+                label: label(key),
+                old: old_content,
+                new: new_content,
+            });
+        }
+    }
+    for (new_sym, &key) in new_syms.iter().zip(new_keys.iter()) {
+        if !old_key_set.contains(&key) {
+            differences.push(Difference::Added {
+                label: label(key),
+                content: content(new, new_sym)?,
+            });
+        }
+    }
+
+    Ok(differences)
+}
+
+/// Returns the name of each symbol along with its position among the
+/// symbols of that name, starting from 1, which together identify a symbol
+/// in a file even if its name isn't unique.
+fn occurrence_keys<'a>(symbols: &[&'a SymbolInfo]) -> Vec<(&'a str, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    symbols
+        .iter()
+        .map(|sym| {
+            let count = counts.entry(&sym.name).or_insert(0);
+            *count += 1;
+            (&sym.name[..], *count)
+        })
+        .collect()
+}
+
+/// Returns the symbols that represent data or values, ignoring those which
+/// just describe the structure of the file.
+fn comparable_symbols<'a>(reader: &'a Reader) -> Vec<&'a SymbolInfo> {
+    reader
+        .symbols()
+        .iter()
+        .filter(|sym| !sym.name.is_empty() && sym.typ != STT_SECTION && sym.typ != STT_FILE)
+        .collect()
+}
+
+/// What a symbol refers to, for comparison purposes.
+#[derive(PartialEq, Debug)]
+enum Content {
+    /// The SHA-256 hash of the symbol's bytes.
+    Data { size: u64, hash: String },
+
+    /// The value of a symbol that doesn't refer to any bytes in the file.
+    Value(u64),
+}
+
+impl Content {
+    fn digest(&self) -> String {
+        match self {
+            Content::Data { hash, .. } => format!("sha256 {}", hash),
+            Content::Value(v) => format!("value {:#x}", v),
+        }
+    }
+}
+
+impl std::fmt::Display for Content {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Content::Data { size, hash } => write!(f, "({} bytes, sha256 {})", size, hash),
+            Content::Value(v) => write!(f, "(value {:#x})", v),
+        }
+    }
+}
+
+fn content(reader: &Reader, sym: &SymbolInfo) -> Result<Content, Error> {
+    match sym.section {
+        SymbolSection::Index(_) => {
+            // Symbols without any contents, such as the __bss_start that
+            // linkers define, can lie outside of their sections, and so we
+            // don't ask the reader for their (empty) contents.
+            let mut data = Vec::new();
+            if sym.size > 0 {
+                reader.symbol_reader(sym)?.read_to_end(&mut data)?;
+            }
+            let hash: String = Sha256::digest(&data)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            Ok(Content::Data {
+                size: sym.size,
+                hash,
+            })
+        }
+        _ => Ok(Content::Value(sym.value)),
+    }
+}
+
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elfbin::{Builder, Class, Encoding, Header};
+
+    fn object(machine: u16, symbols: &[(&str, &[u8])], count: u64) -> Result<Vec<u8>, Error> {
+        let hdr = Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine,
+            flags: 0,
+        };
+        let mut builder = Builder::new(hdr, Vec::new())?;
+        for &(name, data) in symbols.iter() {
+            builder.add_symbol(name, data)?;
+        }
+        builder.add_absolute_symbol("COUNT", count, None)?;
+        builder.close()
+    }
+
+    /// Returns the kind and label of a difference.
+    fn summary(difference: &Difference) -> (&str, &str) {
+        match difference {
+            Difference::Header { field, .. } => ("header", field),
+            Difference::Removed { label, .. } => ("removed", label),
+            Difference::Added { label, .. } => ("added", label),
+            Difference::Resized { label, .. } => ("resized", label),
+            Difference::Changed { label, .. } => ("changed", label),
+        }
+    }
+
+    #[test]
+    fn compare_classifies_differences() -> Result<(), Error> {
+        let old_buf = object(
+            62, // x86_64
+            &[
+                ("same", b"same"),
+                ("gone", b"x"),
+                ("grow", b"ab"),
+                ("edit", b"abcd"),
+            ],
+            3,
+        )?;
+        let new_buf = object(
+            183, // AArch64
+            &[
+                ("fresh", b"new"),
+                ("edit", b"abce"),
+                ("grow", b"abc"),
+                ("same", b"same"),
+            ],
+            4,
+        )?;
+        let old = Reader::new(&old_buf)?;
+        let new = Reader::new(&new_buf)?;
+
+        let differences = compare(&old, &new)?;
+        let summaries: Vec<(&str, &str)> = differences.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            vec![
+                ("header", "machine"),
+                ("removed", "gone"),
+                ("resized", "grow"),
+                ("changed", "edit"),
+                ("changed", "COUNT"),
+                ("added", "fresh"),
+            ]
+        );
+        assert_eq!(differences[0].to_string(), "header   machine  0x3e -> 0xb7");
+        assert_eq!(
+            differences[4].to_string(),
+            "changed  COUNT  value 0x3 -> value 0x4"
+        );
+        assert!(differences[2]
+            .to_string()
+            .starts_with("resized  grow  2 -> 3 bytes  (sha256 "));
+
+        assert_eq!(compare(&old, &old)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn compare_duplicate_names_in_order() -> Result<(), Error> {
+        // The linked fixture has two local symbols named _ZL5table, and
+        // we'll change only the contents of the second.
+        let old_buf = include_bytes!("../../testdata/linked-x86_64.elf");
+        let old = Reader::new(&old_buf[..])?;
+        let second = old
+            .symbols()
+            .iter()
+            .filter(|sym| sym.name == "_ZL5table")
+            .nth(1)
+            .unwrap();
+        let section = match second.section {
+            SymbolSection::Index(idx) => &old.sections()[idx],
+            _ => panic!("_ZL5table isn't in a section"),
+        };
+        let mut new_buf = old_buf.to_vec();
+        new_buf[(section.offset + second.value - section.addr) as usize] ^= 0xff;
+        let new = Reader::new(&new_buf)?;
+
+        let differences = compare(&old, &new)?;
+        let summaries: Vec<(&str, &str)> = differences.iter().map(summary).collect();
+        assert_eq!(summaries, vec![("changed", "_ZL5table[2]")]);
+        Ok(())
+    }
+
+    #[test]
+    fn compare_thread_local_symbols() -> Result<(), Error> {
+        // The values of thread-local symbols aren't addresses, so this
+        // checks that their contents are still found.
+        let old_buf = include_bytes!("../../testdata/linked-tls-x86_64.elf");
+        let old = Reader::new(&old_buf[..])?;
+        assert_eq!(compare(&old, &old)?, vec![]);
+
+        let tdata = old.section(".tdata").unwrap();
+        let first_tls = old.symbol("first_tls").unwrap();
+        let mut new_buf = old_buf.to_vec();
+        new_buf[(tdata.offset + first_tls.value) as usize] ^= 0xff;
+        let new = Reader::new(&new_buf)?;
+
+        let differences = compare(&old, &new)?;
+        let summaries: Vec<(&str, &str)> = differences.iter().map(summary).collect();
+        assert_eq!(summaries, vec![("changed", "first_tls")]);
+        Ok(())
+    }
+
+    #[test]
+    fn exit_status() -> Result<(), Error> {
+        let dir = std::env::temp_dir().join(format!("elfbin-diff-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let old = dir.join("old.o");
+        let new = dir.join("new.o");
+        std::fs::write(&old, object(62, &[("logo", b"logo")], 1)?)?;
+        std::fs::write(&new, object(62, &[("logo", b"LOGO")], 1)?)?;
+
+        let status = |old: &PathBuf, new: &PathBuf| {
+            run(DiffCommand {
+                old: old.clone(),
+                new: new.clone(),
+            })
+        };
+        let same = status(&old, &old);
+        let different = status(&old, &new);
+        let missing = status(&old, &dir.join("missing.o"));
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(same?, 0);
+        assert_eq!(different?, 1);
+        // The caller exits with status 2 for errors.
+        assert!(missing.is_err());
+        Ok(())
+    }
+}
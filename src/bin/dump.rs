@@ -0,0 +1,367 @@
+//! The `elfbin dump` subcommand, which describes the contents of an ELF file.
+
+use elfbin::reader::{SectionInfo, SymbolInfo, SymbolSection};
+use elfbin::{Binding, Class, Encoding, Reader, Visibility};
+use std::io::{Error, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(about = "Describe the header, sections and symbols of an ELF file")]
+pub struct DumpCommand {
+    #[structopt(long, name = "json", help = "Print the description as JSON")]
+    pub json: bool,
+
+    #[structopt(name = "FILE", help = "The ELF file to describe")]
+    pub file: PathBuf,
+}
+
+pub fn run(args: DumpCommand) -> Result<(), Error> {
+    let buf = std::fs::read(&args.file)?;
+    let reader = Reader::new(&buf)?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    if args.json {
+        write_json(&reader, &mut out)?;
+    } else {
+        write_text(&reader, &mut out)?;
+    }
+    out.flush()
+}
+
+fn write_text<W: Write>(reader: &Reader, w: &mut W) -> Result<(), Error> {
+    let hdr = reader.header();
+    writeln!(w, "Class:    {}", class_name(hdr.class))?;
+    writeln!(w, "Encoding: {}", encoding_name(hdr.encoding))?;
+    writeln!(
+        w,
+        "Machine:  {} ({:#x})",
+        machine_name(hdr.machine),
+        hdr.machine
+    )?;
+    writeln!(w, "Flags:    {:#010x}", hdr.flags)?;
+    writeln!(w, "Type:     {}", file_type_name(reader.file_type()))?;
+
+    writeln!(w)?;
+    writeln!(w, "Sections:")?;
+    writeln!(
+        w,
+        "  {:>4}  {:<20} {:<10} {:<6} {:>10} {:>10} {:>6}",
+        "Nr", "Name", "Type", "Flags", "Offset", "Size", "Align"
+    )?;
+    for sect in reader.sections() {
+        writeln!(
+            w,
+            "  {:>4}  {:<20} {:<10} {:<6} {:>#10x} {:>10} {:>6}",
+            sect.index,
+            sect.name,
+            section_type_name(sect.typ),
+            section_flags(sect.flags),
+            sect.offset,
+            sect.size,
+            sect.alignment,
+        )?;
+    }
+
+    writeln!(w)?;
+    writeln!(w, "Symbols:")?;
+    writeln!(
+        w,
+        "  {:>18} {:>10} {:>6}  {:<7} {:<6} {:<9} {:<20} Name",
+        "Value", "Size", "Align", "Type", "Bind", "Vis", "Section"
+    )?;
+    for sym in reader.symbols() {
+        writeln!(
+            w,
+            "  {:>#18x} {:>10} {:>6}  {:<7} {:<6} {:<9} {:<20} {}",
+            sym.value,
+            sym.size,
+            sym.alignment,
+            symbol_type_name(sym.typ),
+            binding_name(sym.binding),
+            visibility_name(sym.visibility),
+            symbol_section_name(reader, sym),
+            sym.name,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_json<W: Write>(reader: &Reader, w: &mut W) -> Result<(), Error> {
+    let hdr = reader.header();
+    writeln!(w, "{{")?;
+    writeln!(w, "  \"class\": {},", json_str(class_name(hdr.class)))?;
+    writeln!(
+        w,
+        "  \"encoding\": {},",
+        json_str(encoding_name(hdr.encoding))
+    )?;
+    writeln!(w, "  \"machine\": {},", hdr.machine)?;
+    writeln!(
+        w,
+        "  \"machine_name\": {},",
+        json_str(&machine_name(hdr.machine))
+    )?;
+    writeln!(w, "  \"flags\": {},", hdr.flags)?;
+    writeln!(
+        w,
+        "  \"type\": {},",
+        json_str(&file_type_name(reader.file_type()))
+    )?;
+
+    writeln!(w, "  \"sections\": [")?;
+    let sections = reader.sections();
+    for (i, sect) in sections.iter().enumerate() {
+        write!(w, "    ")?;
+        write_section_json(sect, w)?;
+        writeln!(w, "{}", if i + 1 < sections.len() { "," } else { "" })?;
+    }
+    writeln!(w, "  ],")?;
+
+    writeln!(w, "  \"symbols\": [")?;
+    let symbols = reader.symbols();
+    for (i, sym) in symbols.iter().enumerate() {
+        write!(w, "    ")?;
+        write_symbol_json(reader, sym, w)?;
+        writeln!(w, "{}", if i + 1 < symbols.len() { "," } else { "" })?;
+    }
+    writeln!(w, "  ]")?;
+    writeln!(w, "}}")?;
+    Ok(())
+}
+
+fn write_section_json<W: Write>(sect: &SectionInfo, w: &mut W) -> Result<(), Error> {
+    write!(
+        w,
+        "{{\"index\": {}, \"name\": {}, \"type\": {}, \"flags\": {}, \"offset\": {}, \"size\": {}, \"alignment\": {}}}",
+        sect.index,
+        json_str(&sect.name),
+        json_str(&section_type_name(sect.typ)),
+        json_str(&section_flags(sect.flags)),
+        sect.offset,
+        sect.size,
+        sect.alignment,
+    )
+}
+
+fn write_symbol_json<W: Write>(reader: &Reader, sym: &SymbolInfo, w: &mut W) -> Result<(), Error> {
+    let section_index = match sym.section {
+        SymbolSection::Index(idx) => idx.to_string(),
+        _ => String::from("null"),
+    };
+    write!(
+        w,
+        "{{\"name\": {}, \"value\": {}, \"size\": {}, \"alignment\": {}, \"type\": {}, \"binding\": {}, \"visibility\": {}, \"section\": {}, \"section_index\": {}}}",
+        json_str(&sym.name),
+        sym.value,
+        sym.size,
+        sym.alignment,
+        json_str(&symbol_type_name(sym.typ)),
+        json_str(&binding_name(sym.binding)),
+        json_str(visibility_name(sym.visibility)),
+        json_str(&symbol_section_name(reader, sym)),
+        section_index,
+    )
+}
+
+/// Returns the given string as a quoted JSON string.
+fn json_str(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+fn class_name(class: Class) -> &'static str {
+    match class {
+        Class::ELF32 => "ELF32",
+        Class::ELF64 => "ELF64",
+    }
+}
+
+fn encoding_name(encoding: Encoding) -> &'static str {
+    match encoding {
+        Encoding::LSB => "LSB",
+        Encoding::MSB => "MSB",
+    }
+}
+
+/// Returns the name of the given machine, using the same names that the
+/// `--machine` option accepts where possible.
+fn machine_name(machine: u16) -> String {
+    let name = match machine {
+        0 => "none",
+        3 => "x86",
+        4 => "68k",
+        8 => "mips",
+        20 => "ppc",
+        21 => "ppc64",
+        40 => "arm",
+        62 => "x86_64",
+        83 => "avr",
+        183 => "aarch64",
+        243 => "riscv",
+        _ => return format!("{:#06x}", machine),
+    };
+    String::from(name)
+}
+
+fn file_type_name(typ: u16) -> String {
+    let name = match typ {
+        0 => "NONE",
+        1 => "REL",
+        2 => "EXEC",
+        3 => "DYN",
+        4 => "CORE",
+        _ => return format!("{:#06x}", typ),
+    };
+    String::from(name)
+}
+
+fn section_type_name(typ: u32) -> String {
+    let name = match typ {
+        0 => "NULL",
+        1 => "PROGBITS",
+        2 => "SYMTAB",
+        3 => "STRTAB",
+        4 => "RELA",
+        5 => "HASH",
+        6 => "DYNAMIC",
+        7 => "NOTE",
+        8 => "NOBITS",
+        9 => "REL",
+        11 => "DYNSYM",
+        _ => return format!("{:#x}", typ),
+    };
+    String::from(name)
+}
+
+/// Returns the given section flags as letters, like `readelf` does.
+fn section_flags(flags: u64) -> String {
+    let letters = [
+        (0x1, 'W'),  // SHF_WRITE
+        (0x2, 'A'),  // SHF_ALLOC
+        (0x4, 'X'),  // SHF_EXECINSTR
+        (0x10, 'M'), // SHF_MERGE
+        (0x20, 'S'), // SHF_STRINGS
+        (0x40, 'I'), // SHF_INFO_LINK
+    ];
+    letters
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, letter)| letter)
+        .collect()
+}
+
+fn symbol_type_name(typ: u8) -> String {
+    let name = match typ {
+        0 => "NOTYPE",
+        1 => "OBJECT",
+        2 => "FUNC",
+        3 => "SECTION",
+        4 => "FILE",
+        5 => "COMMON",
+        6 => "TLS",
+        _ => return typ.to_string(),
+    };
+    String::from(name)
+}
+
+fn binding_name(binding: Binding) -> String {
+    let name = match binding {
+        Binding::Local => "LOCAL",
+        Binding::Global => "GLOBAL",
+        Binding::Weak => "WEAK",
+        Binding::Other(10) => "UNIQUE",
+        Binding::Other(value) => return value.to_string(),
+    };
+    String::from(name)
+}
+
+fn visibility_name(visibility: Visibility) -> &'static str {
+    match visibility {
+        Visibility::Default => "DEFAULT",
+        Visibility::Internal => "INTERNAL",
+        Visibility::Hidden => "HIDDEN",
+        Visibility::Protected => "PROTECTED",
+    }
+}
+
+fn symbol_section_name(reader: &Reader, sym: &SymbolInfo) -> String {
+    match sym.section {
+        SymbolSection::Undefined => String::from("UND"),
+        SymbolSection::Absolute => String::from("ABS"),
+        SymbolSection::Common => String::from("COM"),
+        SymbolSection::Index(idx) => reader.sections()[idx].name.clone(),
+        SymbolSection::Reserved(idx) => format!("{:#x}", idx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elfbin::{Builder, Header};
+
+    #[test]
+    fn json_str_escapes() {
+        assert_eq!(json_str(""), r#""""#);
+        assert_eq!(json_str("logo"), r#""logo""#);
+        assert_eq!(json_str("say \"hi\""), r#""say \"hi\"""#);
+        assert_eq!(json_str("C:\\assets"), r#""C:\\assets""#);
+        assert_eq!(json_str("a\nb\rc\td"), r#""a\nb\rc\td""#);
+        assert_eq!(json_str("\u{1}\u{1f}\u{7f}"), "\"\\u0001\\u001f\u{7f}\"");
+        assert_eq!(json_str("ünïcödé"), r#""ünïcödé""#);
+    }
+
+    #[test]
+    fn json_layout() -> Result<(), Error> {
+        let hdr = Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 40, // ARM
+            flags: 0x05000000,
+        };
+        let mut builder = Builder::new(hdr, Vec::new())?;
+        builder.set_section_name(".ro\"data\t1");
+        builder.add_symbol_align("say \"hi\"\n\u{1}", 4, &b"hi"[..])?;
+        let buf = builder.close()?;
+        let reader = Reader::new(&buf)?;
+
+        let mut out: Vec<u8> = Vec::new();
+        write_json(&reader, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{
+  "class": "ELF32",
+  "encoding": "LSB",
+  "machine": 40,
+  "machine_name": "arm",
+  "flags": 83886080,
+  "type": "REL",
+  "sections": [
+    {"index": 0, "name": "", "type": "NULL", "flags": "", "offset": 0, "size": 0, "alignment": 0},
+    {"index": 1, "name": ".shstrtab", "type": "STRTAB", "flags": "S", "offset": 56, "size": 38, "alignment": 0},
+    {"index": 2, "name": ".ro\"data\t1", "type": "PROGBITS", "flags": "A", "offset": 52, "size": 2, "alignment": 4},
+    {"index": 3, "name": ".strtab", "type": "STRTAB", "flags": "S", "offset": 96, "size": 12, "alignment": 0},
+    {"index": 4, "name": ".symtab", "type": "SYMTAB", "flags": "", "offset": 108, "size": 32, "alignment": 0}
+  ],
+  "symbols": [
+    {"name": "say \"hi\"\n\u0001", "value": 0, "size": 2, "alignment": 4, "type": "OBJECT", "binding": "GLOBAL", "visibility": "DEFAULT", "section": ".ro\"data\t1", "section_index": 2}
+  ]
+}
+"#
+        );
+        Ok(())
+    }
+}
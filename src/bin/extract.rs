@@ -0,0 +1,273 @@
+//! The `elfbin extract` subcommand, which writes the contents of data symbols
+//! back out to files.
+
+use elfbin::reader::{SymbolInfo, SymbolSection};
+use elfbin::Reader;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(about = "Write the contents of data symbols in an ELF file to files")]
+pub struct ExtractCommand {
+    #[structopt(
+        short,
+        long,
+        name = "dir",
+        help = "Directory to write the files into",
+        default_value = "."
+    )]
+    pub dir: PathBuf,
+
+    #[structopt(name = "FILE", help = "The ELF file to extract from")]
+    pub file: PathBuf,
+
+    #[structopt(
+        name = "NAME",
+        help = "Extract only the symbols with these names, which may contain * and ? wildcards [default: all data symbols]"
+    )]
+    pub names: Vec<String>,
+}
+
+pub fn run(args: ExtractCommand) -> Result<(), Error> {
+    let buf = std::fs::read(&args.file)?;
+    let reader = Reader::new(&buf)?;
+
+    let data_symbols = data_symbols(reader.symbols());
+    let mut selected: Vec<&SymbolInfo> = Vec::new();
+    if args.names.is_empty() {
+        selected.extend(data_symbols.iter());
+    } else {
+        for pattern in args.names.iter() {
+            let matches: Vec<&SymbolInfo> = data_symbols
+                .iter()
+                .filter(|sym| glob_match(pattern, &sym.name))
+                .collect();
+            if matches.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("no data symbol matches {:?}", pattern),
+                ));
+            }
+            for sym in matches {
+                if !selected.iter().any(|selected| std::ptr::eq(*selected, sym)) {
+                    selected.push(sym);
+                }
+            }
+        }
+    }
+
+    let file_names = file_names(&selected)?;
+    for (sym, file_name) in selected.into_iter().zip(file_names) {
+        let path = args.dir.join(file_name);
+        let mut src = reader.symbol_reader(sym)?;
+        let mut f = File::create(&path)?;
+        std::io::copy(&mut src, &mut f)?;
+        println!("{} ({} bytes)", path.display(), sym.size);
+    }
+
+    Ok(())
+}
+
+/// Returns the symbols that describe data, which are those defined in a
+/// section that are either objects or untyped symbols with a nonzero size.
+/// Other symbols, such as the end markers defined by --companions, don't
+/// describe any data.
+///
+/// `objcopy -I binary` and `ld -b binary` define only untyped symbols of
+/// size zero, marking the start and end of each input file's data as
+/// `_binary_NAME_start` and `_binary_NAME_end`. So that those files can be
+/// extracted too, each such start symbol with a matching end symbol is
+/// given the size of the data between them.
+fn data_symbols(symbols: &[SymbolInfo]) -> Vec<SymbolInfo> {
+    symbols
+        .iter()
+        .filter(|sym| matches!(sym.section, SymbolSection::Index(_)))
+        .filter_map(|sym| match sym.typ {
+            STT_OBJECT => Some(sym.clone()),
+            STT_NOTYPE if sym.size > 0 => Some(sym.clone()),
+            STT_NOTYPE => {
+                let stem = sym.name.strip_suffix("_start")?;
+                if !stem.starts_with("_binary_") {
+                    return None;
+                }
+                let end_name = format!("{}_end", stem);
+                let end = symbols.iter().find(|end| {
+                    end.name == end_name && end.section == sym.section && end.value >= sym.value
+                })?;
+                Some(SymbolInfo {
+                    size: end.value - sym.value,
+                    ..sym.clone()
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the name of the file to write each of the given symbols into.
+///
+/// This is usually just the symbol name, but linked files often have several
+/// local symbols of the same name, and so those get a suffix with their
+/// position among the symbols of that name, starting from 1: for example,
+/// `table~1` and `table~2`. The suffix uses `~` because it can't appear in
+/// the names of C symbols, unlike the `.` in the names that GCC gives to
+/// function-local statics, such as `lock.0`.
+fn file_names(symbols: &[&SymbolInfo]) -> Result<Vec<String>, Error> {
+    let mut file_names: Vec<String> = Vec::with_capacity(symbols.len());
+    for (i, sym) in symbols.iter().enumerate() {
+        if sym.name.is_empty()
+            || sym.name == "."
+            || sym.name == ".."
+            || sym.name.contains(['/', '\\'])
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("symbol name {:?} can't be used as a filename", sym.name),
+            ));
+        }
+        let same_name = |other: &&&SymbolInfo| other.name == sym.name;
+        let file_name = if symbols.iter().filter(same_name).count() > 1 {
+            let occurrence = symbols[..i].iter().filter(same_name).count() + 1;
+            format!("{}~{}", sym.name, occurrence)
+        } else {
+            sym.name.clone()
+        };
+        if file_names.contains(&file_name) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("more than one symbol would be written to {:?}", file_name),
+            ));
+        }
+        file_names.push(file_name);
+    }
+    Ok(file_names)
+}
+
+/// Returns true if the given name matches the given pattern, in which `*`
+/// matches any sequence of characters and `?` matches any one character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // This is the usual backtracking approach, where we remember the most
+    // recent star so that we can let it match one more character if the
+    // rest of the pattern fails to match.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_patterns() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "logo"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "logo"));
+        assert!(glob_match("logo", "logo"));
+        assert!(!glob_match("logo", "log"));
+        assert!(!glob_match("log", "logo"));
+        assert!(glob_match("l?go", "logo"));
+        assert!(!glob_match("l?go", "lgo"));
+        assert!(glob_match("sprite_*", "sprite_"));
+        assert!(glob_match("sprite_*", "sprite_walk"));
+        assert!(!glob_match("sprite_*", "font_sprite_walk"));
+        assert!(glob_match("*.png", "title.png"));
+        assert!(glob_match("*_??", "music_01"));
+        assert!(!glob_match("*_??", "music_1"));
+
+        // These need the star to give back characters it first matched.
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("a*b*c", "abbbcbc"));
+        assert!(glob_match("*a*a*a", "banana"));
+        assert!(!glob_match("*a*a*a*a", "banana"));
+        assert!(glob_match("**x", "yyx"));
+    }
+
+    #[test]
+    fn untyped_data_symbols() -> Result<(), Error> {
+        // The fixture has an untyped symbol with a size, and the untyped
+        // start and end symbols that ld -b binary defines for a file.
+        let buf = include_bytes!("../../testdata/binary-x86_64.o");
+        let reader = Reader::new(&buf[..])?;
+        let symbols = data_symbols(reader.symbols());
+        let summary: Vec<(&str, u64)> = symbols
+            .iter()
+            .map(|sym| (&sym.name[..], sym.size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("sized", 5), ("_binary_greeting_txt_start", 6)]
+        );
+        for (sym, expected) in symbols.iter().zip([&b"sized"[..], &b"hello\n"[..]].iter()) {
+            let mut data = Vec::new();
+            std::io::Read::read_to_end(&mut reader.symbol_reader(sym)?, &mut data)?;
+            assert_eq!(&data[..], *expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_names_get_suffixes() -> Result<(), Error> {
+        // The linked fixture has two local symbols named _ZL5table.
+        let buf = include_bytes!("../../testdata/linked-x86_64.elf");
+        let reader = Reader::new(&buf[..])?;
+        let symbols: Vec<&SymbolInfo> = reader
+            .symbols()
+            .iter()
+            .filter(|sym| sym.name == "_ZL5table" || sym.name == "_ZN7CounterIiE5countE")
+            .collect();
+        assert_eq!(
+            file_names(&symbols)?,
+            vec!["_ZL5table~1", "_ZL5table~2", "_ZN7CounterIiE5countE"]
+        );
+        for (sym, expected) in symbols.iter().zip([[1, 2, 3, 4], [5, 6, 7, 8]].iter()) {
+            let mut data = Vec::new();
+            std::io::Read::read_to_end(&mut reader.symbol_reader(sym)?, &mut data)?;
+            assert_eq!(&data[..], &expected[..]);
+        }
+
+        // Names like those GCC gives to function-local statics don't clash
+        // with the suffixes.
+        let mut renamed = symbols[2].clone();
+        renamed.name = String::from("_ZL5table.2");
+        assert_eq!(
+            file_names(&[symbols[0], symbols[1], &renamed])?,
+            vec!["_ZL5table~1", "_ZL5table~2", "_ZL5table.2"]
+        );
+
+        // A suffixed name mustn't overwrite a symbol that has that name.
+        renamed.name = String::from("_ZL5table~2");
+        let err = file_names(&[symbols[0], symbols[1], &renamed]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        Ok(())
+    }
+}
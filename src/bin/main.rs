@@ -1,43 +1,203 @@
 //! Creates ELF files containing data from other files.
 
+mod diff;
+mod dump;
+mod extract;
+
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::Error;
 use std::io::ErrorKind::InvalidInput;
-use std::path::PathBuf;
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use structopt::StructOpt;
 
-fn main() -> Result<(), Error> {
-    let args = CommandLine::from_args();
+fn main() {
+    // The subcommands are recognized before parsing the main command line
+    // so that they don't conflict with its NAME=FILE arguments.
+    let subcommand = std::env::args_os().nth(1);
+    let result = match subcommand.as_deref().and_then(OsStr::to_str) {
+        Some("diff") => {
+            // Like diff(1), this exits with status 1 if the files differ and
+            // 2 if they couldn't be compared.
+            match diff::run(StructOpt::from_iter(subcommand_args("diff"))) {
+                Ok(status) => std::process::exit(status),
+                Err(err) => {
+                    eprintln!("elfbin: {}", err);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Some("dump") => dump::run(StructOpt::from_iter(subcommand_args("dump"))),
+        Some("extract") => extract::run(StructOpt::from_iter(subcommand_args("extract"))),
+        _ => run(CommandLine::from_args()),
+    };
+    if let Err(err) = result {
+        eprintln!("elfbin: {}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Returns the command line arguments for the given subcommand, with the
+/// program name and subcommand name combined so that they'll be shown
+/// together in usage messages.
+fn subcommand_args(name: &str) -> impl Iterator<Item = OsString> {
+    let program = OsString::from(format!("elfbin {}", name));
+    std::iter::once(program).chain(std::env::args_os().skip(2))
+}
 
-    let of = File::create(args.out)?;
-    let mut builder = elfbin::Builder::new(
-        elfbin::Header {
-            class: args.class,
-            encoding: args.encoding,
-            machine: args.machine,
-            flags: args.flags,
-        },
-        of,
-    )?;
-    if args.section != ".rodata" {
-        builder.set_section_name(&args.section);
+fn run(args: CommandLine) -> Result<(), Error> {
+    let hdr = elfbin::Header {
+        class: args.class,
+        encoding: args.encoding,
+        machine: args.machine,
+        flags: args.flags,
+    };
+    let verify = args.verify;
+    let bindings = Bindings {
+        header: args.header.clone(),
+        cpp_header: args.cpp_header.clone(),
+        cpp_namespace: args.cpp_namespace.clone(),
+        rust_module: args.rust_module.clone(),
+    };
+    let generated = if args.out.as_os_str() == "-" {
+        let mut builder = elfbin::Builder::new(hdr, std::io::stdout())?;
+        add_symbols(&mut builder, args)?;
+        let generated = bindings.generate(&builder)?;
+        close(builder, verify)?;
+        generated
+    } else {
+        let of = File::create(&args.out)?;
+        let mut builder = elfbin::Builder::new(hdr, of)?;
+        add_symbols(&mut builder, args)?;
+        let generated = bindings.generate(&builder)?;
+        let of = close(builder, verify)?;
+        of.sync_all()?;
+        generated
+    };
+    for (path, text) in generated {
+        std::fs::write(path, text)?;
     }
 
-    for sym_def in args.symbols {
-        let name = sym_def.symbol_name;
-        let filename = sym_def.filename;
-        let f = File::open(filename)?;
-        builder.add_symbol(name, f)?;
+    Ok(())
+}
+
+/// The source files declaring the symbols to write alongside the object
+/// file, as requested on the command line.
+struct Bindings {
+    header: Option<PathBuf>,
+    cpp_header: Option<PathBuf>,
+    cpp_namespace: String,
+    rust_module: Option<PathBuf>,
+}
+
+impl Bindings {
+    /// Returns the path and text of each of the requested files.
+    fn generate<W: Write>(
+        &self,
+        builder: &elfbin::Builder<W>,
+    ) -> Result<Vec<(PathBuf, String)>, Error> {
+        let mut generated = Vec::new();
+        if let Some(path) = &self.header {
+            generated.push((path.clone(), builder.c_header(&guard_name(path))?));
+        }
+        if let Some(path) = &self.cpp_header {
+            let text = builder.cpp_header(&guard_name(path), &self.cpp_namespace)?;
+            generated.push((path.clone(), text));
+        }
+        if let Some(path) = &self.rust_module {
+            generated.push((path.clone(), builder.rust_module()?));
+        }
+        Ok(generated)
     }
+}
 
-    let of = builder.close()?;
-    of.sync_all()?;
+/// Returns the name of the include guard macro for a header, which is
+/// named after the file so that data.h uses DATA_H.
+fn guard_name(path: &Path) -> String {
+    let filename = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut guard: String = filename
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    if !guard.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        guard.insert_str(0, "ELFBIN_");
+    }
+    guard
+}
+
+fn close<W: Write>(builder: elfbin::Builder<W>, verify: bool) -> Result<W, Error> {
+    if verify {
+        builder.close_verified()
+    } else {
+        builder.close()
+    }
+}
+
+fn add_symbols<W: Write>(builder: &mut elfbin::Builder<W>, args: CommandLine) -> Result<(), Error> {
+    if let Some(section) = &args.section {
+        builder.set_section_name(section);
+    }
+    if args.writable {
+        builder.set_section_writable(builder.default_section(), true)?;
+    }
+    if args.section_per_symbol {
+        builder.set_section_per_symbol(true);
+        builder.set_symbol_section_template(args.section_template)?;
+    }
+    if args.dedup {
+        builder.set_deduplicate(true);
+    }
+    if args.c_identifiers {
+        builder.set_require_c_identifiers(true);
+    }
+    if args.cpp_names.is_some() {
+        builder.set_cpp_names(args.cpp_names);
+    }
+    if args.companions {
+        builder.set_companion_symbols(Some(elfbin::CompanionNaming {
+            start: args.start_name,
+            end: args.end_name,
+            size: args.size_name,
+        }))?;
+    }
+
+    if let Some(root_name) = args.vfs {
+        let mut fs = elfbin::Filesystem::new();
+        for sym_def in args.symbols {
+            let f = File::open(sym_def.filename)?;
+            fs.add_file(sym_def.symbol_name, f)?;
+        }
+        builder.add_filesystem(root_name, &fs)?;
+    } else {
+        for sym_def in args.symbols {
+            let name = sym_def.symbol_name;
+            let filename = sym_def.filename;
+            let f = File::open(filename)?;
+            builder.add_symbol(name, f)?;
+        }
+    }
+
+    if builder.deduplicated_bytes() > 0 {
+        eprintln!(
+            "elfbin: saved {} bytes by deduplicating identical symbol data",
+            builder.deduplicated_bytes()
+        );
+    }
 
     Ok(())
 }
 
 #[derive(StructOpt, Debug, Clone)]
+#[structopt(after_help = "SUBCOMMANDS:
+    diff       Compare the symbols of two ELF files
+    dump       Describe the header, sections and symbols of an ELF file
+    extract    Write the contents of data symbols in an ELF file to files
+
+Run elfbin SUBCOMMAND --help for more information about a subcommand.")]
 pub struct CommandLine {
     #[structopt(long, name = "class", help = "ELF Class", parse(try_from_str=parse_class), default_value="ELF64")]
     pub class: elfbin::Class,
@@ -54,15 +214,134 @@ pub struct CommandLine {
     #[structopt(
         long,
         name = "section",
-        help = "Override section name",
-        default_value = ".rodata"
+        help = "Override section name [default: .rodata, or .data if --writable]"
+    )]
+    pub section: Option<String>,
+
+    #[structopt(long, name = "writable", help = "Place the data in a writable section")]
+    pub writable: bool,
+
+    #[structopt(
+        long,
+        name = "section-per-symbol",
+        help = "Place each symbol in its own section, named SECTION.NAME"
+    )]
+    pub section_per_symbol: bool,
+
+    #[structopt(
+        long,
+        name = "section-template",
+        value_name = "template",
+        help = "Name template for sections with --section-per-symbol [default: SECTION.{}]",
+        requires = "section-per-symbol"
+    )]
+    pub section_template: Option<String>,
+
+    #[structopt(
+        long,
+        name = "companions",
+        help = "Also define end and size symbols for each symbol, like objcopy"
+    )]
+    pub companions: bool,
+
+    #[structopt(
+        long,
+        name = "c-identifiers",
+        help = "Require symbol names to be valid C identifiers"
     )]
-    pub section: String,
+    pub c_identifiers: bool,
+
+    #[structopt(
+        long,
+        name = "cpp-names",
+        help = "Name symbols after C++ variables such as assets::logo, with the given element type",
+        parse(try_from_str = parse_cpp_type),
+        conflicts_with_all = &["header", "cpp-header", "rust-module"]
+    )]
+    pub cpp_names: Option<elfbin::CppType>,
+
+    #[structopt(long, name = "dedup", help = "Store identical symbol data only once")]
+    pub dedup: bool,
+
+    #[structopt(
+        long,
+        name = "vfs",
+        help = "Embed the files as a filesystem under a single symbol, using each NAME as a path"
+    )]
+    pub vfs: Option<String>,
+
+    #[structopt(
+        long,
+        name = "header",
+        help = "Also write a C header file declaring the symbols"
+    )]
+    pub header: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        name = "cpp-header",
+        help = "Also write a C++ header file declaring the symbols and accessor functions"
+    )]
+    pub cpp_header: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        name = "cpp-namespace",
+        help = "Namespace for the accessor functions in the C++ header",
+        default_value = "assets"
+    )]
+    pub cpp_namespace: String,
+
+    #[structopt(
+        long,
+        name = "rust-module",
+        help = "Also write a Rust module declaring the symbols and accessor functions, for Rust 1.82 or later"
+    )]
+    pub rust_module: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        name = "verify",
+        help = "Check the structure of the output file before writing it"
+    )]
+    pub verify: bool,
+
+    #[structopt(
+        long,
+        name = "start-name",
+        help = "Name template for start symbols with --companions",
+        default_value = "_binary_{}_start",
+        parse(try_from_str = parse_name_template)
+    )]
+    pub start_name: String,
+
+    #[structopt(
+        long,
+        name = "end-name",
+        help = "Name template for end symbols with --companions",
+        default_value = "_binary_{}_end",
+        parse(try_from_str = parse_name_template)
+    )]
+    pub end_name: String,
+
+    #[structopt(
+        long,
+        name = "size-name",
+        help = "Name template for size symbols with --companions",
+        default_value = "_binary_{}_size",
+        parse(try_from_str = parse_name_template)
+    )]
+    pub size_name: String,
 
     #[structopt(name = "NAME=FILE", help = "Define a symbol")]
     pub symbols: Vec<SymbolDef>,
 
-    #[structopt(short, name = "out", help = "Output filename", required = true)]
+    #[structopt(
+        short,
+        name = "out",
+        help = "Output filename, or - for standard output",
+        required = true
+    )]
     pub out: PathBuf,
 }
 
@@ -151,6 +430,39 @@ fn parse_machine(src: &str) -> Result<u16, Error> {
     }
 }
 
+fn parse_cpp_type(src: &str) -> Result<elfbin::CppType, Error> {
+    match src {
+        "char" => Ok(elfbin::CppType::Char),
+        "signed char" => Ok(elfbin::CppType::SignedChar),
+        "unsigned char" => Ok(elfbin::CppType::UnsignedChar),
+        "short" => Ok(elfbin::CppType::Short),
+        "unsigned short" => Ok(elfbin::CppType::UnsignedShort),
+        "int" => Ok(elfbin::CppType::Int),
+        "unsigned int" => Ok(elfbin::CppType::UnsignedInt),
+        "long" => Ok(elfbin::CppType::Long),
+        "unsigned long" => Ok(elfbin::CppType::UnsignedLong),
+        "long long" => Ok(elfbin::CppType::LongLong),
+        "unsigned long long" => Ok(elfbin::CppType::UnsignedLongLong),
+        "float" => Ok(elfbin::CppType::Float),
+        "double" => Ok(elfbin::CppType::Double),
+        _ => Err(Error::new(
+            InvalidInput,
+            "element type must be a fundamental C++ type, such as char or unsigned int",
+        )),
+    }
+}
+
+fn parse_name_template(src: &str) -> Result<String, Error> {
+    if src.contains("{}") {
+        Ok(String::from(src))
+    } else {
+        Err(Error::new(
+            InvalidInput,
+            "name template must contain {} for the symbol name",
+        ))
+    }
+}
+
 fn parse_flags(src: &str) -> Result<u32, Error> {
     if let Some(digits) = src.strip_prefix("0x") {
         match u32::from_str_radix(digits, 16) {
@@ -0,0 +1,303 @@
+//! Generating C header files that declare the symbols of a [`Builder`].
+
+use crate::io::{Error, ErrorKind, Result, Write};
+use crate::{is_c_identifier, Binding, Builder, SectionContents, SymbolNameError};
+use crate::{FIRST_DATA_SECTION_IDX, SHN_ABS, SHN_UNDEF, STT_OBJECT};
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A symbol that code linked with the object file can refer to.
+pub(crate) struct Declaration<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) kind: DeclarationKind,
+    pub(crate) size: u64,
+    pub(crate) alignment: u64,
+
+    /// True if the symbol is in a section that the program may modify.
+    pub(crate) writable: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum DeclarationKind {
+    /// The symbol refers to `size` bytes of data.
+    Data,
+
+    /// The symbol refers to a null-terminated string of `size` bytes,
+    /// including the terminator.
+    String,
+
+    /// The symbol marks a position in a section without referring to any
+    /// data, like the end symbols of [`CompanionNaming`](crate::CompanionNaming).
+    Marker,
+
+    /// The symbol is an absolute symbol with the given value.
+    Absolute(u64),
+}
+
+impl<W: Write> Builder<W> {
+    /// Returns a C header file declaring each of the global and weak symbols
+    /// that have been added to the builder so far, using the given name for
+    /// the macro in its include guard.
+    ///
+    /// Each data symbol is declared as an `extern` array of `uint8_t`, or of
+    /// `char` for [string symbols](Self::add_string_symbol), which is `const`
+    /// unless the symbol is in a writable or zeroed section. A macro named
+    /// after the symbol in upper case with a `_SIZE` suffix gives the size
+    /// of the data in bytes, and when compiling with GCC or Clang the
+    /// declaration tells the compiler the symbol's alignment. Any leading
+    /// underscores are left out of the macro name, because names starting
+    /// with an underscore and an upper case letter are reserved: the macro
+    /// for `_binary_logo_start` is `BINARY_LOGO_START_SIZE`.
+    ///
+    /// Local symbols aren't declared because they can't be referred to from
+    /// other files, and absolute symbols are declared as arrays whose
+    /// address is the symbol's value.
+    ///
+    /// Returns an error wrapping [`SymbolNameError::NotCIdentifier`] if any
+    /// symbol name or the guard isn't a valid C identifier, or an error of
+    /// kind [`ErrorKind::InvalidInput`] if a name is a C keyword, if the
+    /// guard or a size macro would have a reserved name, if two names in
+    /// the header would be the same, such as a size macro and another
+    /// symbol, or if [C++ naming](Self::set_cpp_names) is enabled.
+    pub fn c_header(&self, guard: &str) -> Result<String> {
+        self.check_unmangled("C header")?;
+        let decls = self.declarations();
+
+        // Every name in the header must be distinct, or the macros would
+        // replace the names of the symbols.
+        let mut names: Vec<String> = vec![String::from(guard), String::from(ALIGNED_MACRO_NAME)];
+        let mut macros: Vec<String> = vec![String::from(guard)];
+        for decl in decls.iter() {
+            names.push(String::from(decl.name));
+            if has_size_macro(decl) {
+                names.push(size_macro_name(decl.name));
+                macros.push(size_macro_name(decl.name));
+            }
+        }
+        check_header_names(&names, C_KEYWORDS, "C")?;
+        check_macro_names(&macros)?;
+
+        let mut out = format!(
+            "/* Generated by elfbin. Do not edit. */\n\n#ifndef {0}\n#define {0}\n\n#include <stdint.h>\n\n{1}{2}",
+            guard, ALIGNED_MACRO, C_LINKAGE_START
+        );
+        for decl in decls.iter() {
+            out.push('\n');
+            if has_size_macro(decl) {
+                out.push_str(&format!(
+                    "#define {} {}\n",
+                    size_macro_name(decl.name),
+                    decl.size
+                ));
+            }
+            if let DeclarationKind::Absolute(value) = decl.kind {
+                out.push_str(&format!(
+                    "/* Absolute symbol: its address, (uintptr_t){}, is {:#x}. */\n",
+                    decl.name, value
+                ));
+            }
+            let qualifier = if decl.writable { "" } else { "const " };
+            let typ = match decl.kind {
+                DeclarationKind::String => "char",
+                _ => "uint8_t",
+            };
+            out.push_str(&format!("extern {}{} {}[]", qualifier, typ, decl.name));
+            if decl.alignment > 1 {
+                out.push_str(&format!(" ELFBIN_ALIGNED({})", decl.alignment));
+            }
+            out.push_str(";\n");
+        }
+        out.push_str(&format!("{}\n#endif /* {} */\n", C_LINKAGE_END, guard));
+        Ok(out)
+    }
+
+    /// Checks that the symbols can be declared in a generated source file of
+    /// the given kind, which isn't possible when their names are mangled
+    /// because the declarations would use the mangled names.
+    pub(crate) fn check_unmangled(&self, kind: &str) -> Result<()> {
+        if self.cpp_names.is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("can't write a {} for symbols with C++ names", kind),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the symbols that code linked with the object file can refer
+    /// to, in the order they were defined.
+    pub(crate) fn declarations(&self) -> Vec<Declaration<'_>> {
+        self.symbols
+            .iter()
+            .filter(|entry| entry.binding != Binding::Local && entry.section_idx != SHN_UNDEF)
+            .map(|entry| {
+                if entry.section_idx == SHN_ABS {
+                    return Declaration {
+                        name: &entry.name,
+                        kind: DeclarationKind::Absolute(entry.value),
+                        size: entry.size,
+                        alignment: 1,
+                        writable: false,
+                    };
+                }
+                let sect = &self.sections[(entry.section_idx - FIRST_DATA_SECTION_IDX) as usize];
+                let kind = match (&sect.contents, entry.typ) {
+                    (SectionContents::Strings(_), STT_OBJECT) => DeclarationKind::String,
+                    (_, STT_OBJECT) => DeclarationKind::Data,
+                    _ => DeclarationKind::Marker,
+                };
+                Declaration {
+                    name: &entry.name,
+                    kind,
+                    size: entry.size,
+                    alignment: entry.alignment,
+                    writable: sect.writable || matches!(sect.contents, SectionContents::Zeroed(_)),
+                }
+            })
+            .collect()
+    }
+}
+
+fn has_size_macro(decl: &Declaration) -> bool {
+    matches!(decl.kind, DeclarationKind::Data | DeclarationKind::String)
+}
+
+fn size_macro_name(name: &str) -> String {
+    format!("{}_SIZE", name.trim_start_matches('_').to_ascii_uppercase())
+}
+
+/// Checks that the given names, which will all be defined by a generated
+/// header, are valid C identifiers that aren't keywords of the given
+/// language and are distinct from each other.
+pub(crate) fn check_header_names(
+    names: &[String],
+    keywords: &[&str],
+    language: &str,
+) -> Result<()> {
+    let mut seen: BTreeSet<&str> = BTreeSet::new();
+    for name in names.iter() {
+        if !is_c_identifier(name) {
+            return Err(SymbolNameError::NotCIdentifier(name.clone()).into());
+        }
+        if keywords.contains(&&name[..]) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the name {} is a {} keyword", name, language),
+            ));
+        }
+        if !seen.insert(name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the name {} would be defined twice in the header", name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that none of the given macro names are reserved for the compiler
+/// and standard library, as names starting with two underscores or with an
+/// underscore and an upper case letter are.
+pub(crate) fn check_macro_names(names: &[String]) -> Result<()> {
+    for name in names.iter() {
+        let mut chars = name.chars();
+        if chars.next() == Some('_') && matches!(chars.next(), Some('_' | 'A'..='Z')) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the macro name {} is reserved", name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) const ALIGNED_MACRO_NAME: &str = "ELFBIN_ALIGNED";
+
+/// Defines a macro for declaring the alignment of a symbol with compilers
+/// that support it. Each header defines it only if another hasn't already.
+pub(crate) const ALIGNED_MACRO: &str = "#ifndef ELFBIN_ALIGNED
+#if defined(__GNUC__)
+#define ELFBIN_ALIGNED(n) __attribute__((aligned(n)))
+#else
+#define ELFBIN_ALIGNED(n)
+#endif
+#endif
+";
+
+const C_LINKAGE_START: &str = "
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+";
+
+const C_LINKAGE_END: &str = "
+#ifdef __cplusplus
+}
+#endif
+";
+
+/// The keywords of C23, which can't be used as the names of symbols.
+const C_KEYWORDS: &[&str] = &[
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_BitInt",
+    "_Bool",
+    "_Complex",
+    "_Decimal128",
+    "_Decimal32",
+    "_Decimal64",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+];
@@ -0,0 +1,62 @@
+//! Tests that use only the types in [`crate::io`], so that they run both with
+//! and without the `std` feature.
+
+use super::io::{ErrorKind, Read, Result};
+use super::reader::SymbolSection;
+use super::*;
+
+fn header() -> Header {
+    Header {
+        class: Class::ELF32,
+        encoding: Encoding::MSB,
+        machine: 40, // ARM
+        flags: 0x05000000,
+    }
+}
+
+#[test]
+fn round_trip_vec() -> Result<()> {
+    let mut builder = Builder::new(header(), Vec::new())?;
+    builder.add_symbol("logo", &b"logo"[..])?;
+    builder.add_zeroed_symbol("buffer", 64, 8)?;
+    builder.add_string_symbol("greeting", "hello")?;
+    builder.add_absolute_symbol("COUNT", 3, None)?;
+    let buf = builder.close_verified()?;
+
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.header().class, Class::ELF32);
+    assert_eq!(reader.header().encoding, Encoding::MSB);
+    let names: Vec<&str> = reader.symbols().iter().map(|sym| &sym.name[..]).collect();
+    assert_eq!(names, vec!["logo", "buffer", "greeting", "COUNT"]);
+
+    let logo = reader.symbol("logo").unwrap();
+    let mut data = Vec::new();
+    reader.symbol_reader(logo)?.read_to_end(&mut data)?;
+    assert_eq!(data, b"logo");
+    let buffer = reader.symbol("buffer").unwrap();
+    assert_eq!(buffer.size, 64);
+    match buffer.section {
+        SymbolSection::Index(idx) => assert!(reader.sections()[idx].is_zeroed()),
+        other => panic!("buffer is in {:?}", other),
+    }
+    assert_eq!(reader.symbol("COUNT").unwrap().value, 3);
+
+    Ok(())
+}
+
+#[test]
+fn borrowed_writer() -> Result<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut builder = Builder::new(header(), &mut buf)?;
+    builder.add_symbol("logo", &b"logo"[..])?;
+    builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.symbol("logo").unwrap().size, 4);
+
+    match Reader::new(&buf[..16]) {
+        Err(err) => assert_eq!(err.kind(), ErrorKind::InvalidData),
+        Ok(_) => panic!("truncated file should be rejected"),
+    }
+
+    Ok(())
+}
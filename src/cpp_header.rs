@@ -0,0 +1,244 @@
+//! Generating C++ header files that declare the symbols of a [`Builder`]
+//! along with accessor functions in a namespace.
+
+use crate::c_header::{
+    check_header_names, check_macro_names, DeclarationKind, ALIGNED_MACRO, ALIGNED_MACRO_NAME,
+};
+use crate::io::{Error, ErrorKind, Result, Write};
+use crate::Builder;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+impl<W: Write> Builder<W> {
+    /// Returns a C++ header file declaring each of the global and weak
+    /// symbols that have been added to the builder so far, with accessor
+    /// functions for them in the given namespace. The header uses the given
+    /// name for the macro in its include guard.
+    ///
+    /// The symbols themselves are declared as `extern "C"` arrays of
+    /// `std::byte`, in the global namespace. The namespace, which may be
+    /// nested using `::` as in `assets::images`, contains a `constexpr`
+    /// function with the same name as each symbol:
+    ///
+    /// * Data symbols have functions returning a `std::span<const std::byte, N>`
+    ///   whose extent is the size of the data. The span isn't `const` if the
+    ///   symbol is in a writable or zeroed section.
+    /// * [String symbols](Self::add_string_symbol) have functions returning
+    ///   a `std::string_view`, which excludes the null terminator.
+    /// * Symbols that only mark a position, such as the end symbols of
+    ///   [companion symbols](Self::set_companion_symbols), have functions
+    ///   returning a pointer.
+    /// * Absolute symbols have functions returning their value as a
+    ///   `std::uintptr_t`. These aren't `constexpr`, because the value is
+    ///   only known at link time.
+    ///
+    /// The header requires C++20, for `std::span`.
+    ///
+    /// Returns an error wrapping [`SymbolNameError::NotCIdentifier`](crate::SymbolNameError::NotCIdentifier)
+    /// if any symbol name, part of the namespace, or the guard isn't a valid
+    /// C identifier, or an error of kind [`ErrorKind::InvalidInput`](crate::io::ErrorKind::InvalidInput)
+    /// if any of those names is a C++ keyword, if the guard is a reserved
+    /// name, if a symbol has the same name as the guard, a macro the header
+    /// defines, or a namespace the header uses (`std` or the outermost part
+    /// of the given namespace), or if [C++ naming](Self::set_cpp_names) is
+    /// enabled.
+    pub fn cpp_header(&self, guard: &str, namespace: &str) -> Result<String> {
+        self.check_unmangled("C++ header")?;
+        let decls = self.declarations();
+
+        let mut names: Vec<String> = vec![String::from(guard), String::from(ALIGNED_MACRO_NAME)];
+        names.extend(decls.iter().map(|decl| String::from(decl.name)));
+        check_header_names(&names, CPP_KEYWORDS, "C++")?;
+        check_macro_names(&[String::from(guard)])?;
+        let namespace_parts: Vec<String> = namespace.split("::").map(String::from).collect();
+        check_header_names(&namespace_parts, CPP_KEYWORDS, "C++")?;
+
+        // The symbols are declared in the global namespace, and so they
+        // can't share a name with the namespaces declared there.
+        for decl in decls.iter() {
+            if decl.name == "std" || decl.name == namespace_parts[0] {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "the symbol name {} is also the name of a namespace in the header",
+                        decl.name
+                    ),
+                ));
+            }
+        }
+
+        let mut out = format!(
+            "// Generated by elfbin. Do not edit.\n\n#ifndef {0}\n#define {0}\n{1}\n{2}\nextern \"C\" {{\n",
+            guard, CPP_INCLUDES, ALIGNED_MACRO
+        );
+        for decl in decls.iter() {
+            let qualifier = if decl.writable { "" } else { "const " };
+            let typ = match decl.kind {
+                DeclarationKind::String => "char",
+                _ => "std::byte",
+            };
+            out.push_str(&format!("extern {}{} {}[]", qualifier, typ, decl.name));
+            if decl.alignment > 1 {
+                out.push_str(&format!(" ELFBIN_ALIGNED({})", decl.alignment));
+            }
+            out.push_str(";\n");
+        }
+        out.push_str(&format!("}}\n\nnamespace {} {{\n", namespace));
+
+        for decl in decls.iter() {
+            let qualifier = if decl.writable { "" } else { "const " };
+            let name = decl.name;
+            out.push('\n');
+            out.push_str(&match decl.kind {
+                DeclarationKind::Data => format!(
+                    "constexpr std::span<{0}std::byte, {1}> {2}() noexcept {{\n    return std::span<{0}std::byte, {1}>(::{2}, {1});\n}}\n",
+                    qualifier, decl.size, name
+                ),
+                DeclarationKind::String => format!(
+                    "constexpr std::string_view {}() noexcept {{\n    return std::string_view(::{}, {});\n}}\n",
+                    name,
+                    name,
+                    decl.size.saturating_sub(1)
+                ),
+                DeclarationKind::Marker => format!(
+                    "constexpr {0}std::byte *{1}() noexcept {{\n    return ::{1};\n}}\n",
+                    qualifier, name
+                ),
+                DeclarationKind::Absolute(_) => format!(
+                    "inline std::uintptr_t {0}() noexcept {{\n    return reinterpret_cast<std::uintptr_t>(::{0});\n}}\n",
+                    name
+                ),
+            });
+        }
+
+        out.push_str(&format!(
+            "\n}} // namespace {}\n\n#endif // {}\n",
+            namespace, guard
+        ));
+        Ok(out)
+    }
+}
+
+const CPP_INCLUDES: &str = "
+#include <cstddef>
+#include <cstdint>
+#include <span>
+#include <string_view>
+";
+
+/// The keywords of C++23, including the alternative tokens such as `and`,
+/// along with the keywords of C that aren't also keywords of C++, because
+/// the symbols are declared with C linkage.
+const CPP_KEYWORDS: &[&str] = &[
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_BitInt",
+    "_Bool",
+    "_Complex",
+    "_Decimal128",
+    "_Decimal32",
+    "_Decimal64",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char16_t",
+    "char32_t",
+    "char8_t",
+    "class",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "compl",
+    "concept",
+    "const",
+    "const_cast",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
@@ -0,0 +1,149 @@
+//! The I/O types used in the API of [`Builder`](crate::Builder).
+//!
+//! When the `std` feature is enabled, which it is by default, these are just
+//! the types of the same names from `std::io`.
+//!
+//! Without the `std` feature, they are minimal replacements that are
+//! available with only `core` and `alloc`. In that case [`Read`] is
+//! implemented for byte slices and [`Write`] is implemented for `Vec<u8>`,
+//! and you can implement either of them for your own byte sources and sinks.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    /// A specialized `Result` type for elfbin operations.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// The error type for elfbin operations.
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: String,
+    }
+
+    impl Error {
+        /// Creates a new error of the given kind with a message describing
+        /// it.
+        pub fn new<M: fmt::Display>(kind: ErrorKind, message: M) -> Self {
+            Self {
+                kind,
+                message: message.to_string(),
+            }
+        }
+
+        /// Returns the general category of this error.
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.message)
+        }
+    }
+
+    /// General categories of [`Error`].
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        /// An argument was invalid.
+        InvalidInput,
+
+        /// The operation isn't supported for the requested target.
+        Unsupported,
+
+        /// Data being read, such as an ELF file, isn't well-formed.
+        InvalidData,
+
+        /// Any other error, such as from a caller-provided [`Read`] or
+        /// [`Write`] implementation.
+        Other,
+    }
+
+    /// A source of bytes.
+    pub trait Read {
+        /// Reads some bytes into the given buffer, returning how many bytes
+        /// were read. Returns zero only at the end of the source.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        /// Reads all of the remaining bytes, appending them to the given
+        /// vector and returning how many bytes were read.
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let mut chunk = [0_u8; 512];
+            let mut total = 0;
+            loop {
+                let n = self.read(&mut chunk)?;
+                if n == 0 {
+                    return Ok(total);
+                }
+                buf.extend_from_slice(&chunk[..n]);
+                total += n;
+            }
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = core::cmp::min(buf.len(), self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let n = self.len();
+            buf.extend_from_slice(self);
+            *self = &[];
+            Ok(n)
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            (**self).read_to_end(buf)
+        }
+    }
+
+    /// A sink for bytes.
+    pub trait Write {
+        /// Writes all of the given bytes.
+        fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+
+        /// Flushes any buffered bytes to their final destination.
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+}
@@ -6,21 +6,21 @@
 //! it support any other object file formats.
 //!
 //! ```
-//! # fn main() -> std::io::Result<()> {
-//! # let buf: Vec<u8> = Vec::new();
-//! # let mut output_file = std::io::Cursor::new(buf);
+//! # fn main() -> elfbin::io::Result<()> {
+//! # let mut output_file: Vec<u8> = Vec::new();
 //! // Create a new builder, establishing the ELF header values
 //! let mut builder = elfbin::Builder::new(
 //!     elfbin::Header {
 //!         class: elfbin::Class::ELF64,
 //!         encoding: elfbin::Encoding::LSB,
-//!         machine: 64, // x86_64
+//!         machine: 62, // x86_64
 //!         flags: 0,
 //!     },
 //!     &mut output_file,
 //! )?;
 //!
-//! // Defile a symbol from any std::io::Read implementation.
+//! // Define a symbol from any elfbin::io::Read implementation, which is
+//! // std::io::Read when the std feature is enabled.
 //! builder.add_symbol("example", &b"hello!"[..])?;
 //!
 //! // Close the builder to finalize the ELF metadata.
@@ -29,11 +29,37 @@
 //! # }
 //! ```
 
-use binbin::endian::Endian;
-use std::{
-    borrow::Cow,
-    io::{Read, Result, Seek, Write},
-};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use crate::io::{Error, ErrorKind, Read, Result, Write};
+use crate::mangle::{check_element_size, is_cpp_name};
+use crate::writer::Writer;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+mod c_header;
+mod cpp_header;
+pub mod io;
+mod mangle;
+pub mod reader;
+mod rust_module;
+mod verify;
+pub mod vfs;
+mod writer;
+
+#[cfg(feature = "tokio")]
+mod async_builder;
+
+#[cfg(feature = "tokio")]
+pub use async_builder::AsyncBuilder;
+pub use mangle::CppType;
+pub use reader::Reader;
+pub use vfs::Filesystem;
 
 /// ELF file class (32-bit or 64-bit).
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -52,6 +78,7 @@ pub enum Encoding {
 }
 
 /// Represents the main ELF header.
+#[derive(Clone, PartialEq, Debug)]
 pub struct Header {
     /// The ELF file class (32-bit or 64-bit).
     pub class: Class,
@@ -67,21 +94,42 @@ pub struct Header {
 }
 
 /// Represents an ELF file under construction.
-pub struct Builder<W: Write + Seek> {
+///
+/// The entire file is buffered in memory until [`close`](Self::close) is
+/// called, and is then written out in a single pass. The writer therefore
+/// needn't implement `std::io::Seek`, so it can be a pipe, standard output,
+/// or a compressing stream.
+///
+/// Buffering means that a builder holds all of the symbol data added to it
+/// in memory, which is about the size of the output file, and
+/// [`close_verified`](Self::close_verified) briefly needs a second copy.
+/// Symbols in [zeroed sections](Self::add_zeroed_section) take no space. If
+/// the data is too large to hold in memory at once, consider splitting it
+/// across several object files.
+pub struct Builder<W: Write> {
     w: W,
     class: Class,
     encoding: Encoding,
-    headmap: HeaderMap,
-    rodata_pos: u64,
-    current_rodata_offset: u64,
-    symbols: Vec<Symbol>,
-    symbol_names: Vec<String>,
-    shstrtab: Cow<'static, [u8]>,
+    machine: u16,
+    flags: u32,
+    sections: Vec<SectionData>,
+    zeroed_section: Option<Section>,
+    companions: Option<CompanionNaming>,
+    symbols: Vec<SymbolEntry>,
+    symbol_names: BTreeSet<String>,
+    require_c_identifiers: bool,
+    cpp_names: Option<CppType>,
+    blob_index: Option<BlobIndex>,
+    deduplicated_bytes: u64,
+    section_per_symbol: bool,
+    symbol_section_template: Option<String>,
+    string_section: Option<Section>,
+    relocations: Vec<Relocation>,
 }
 
 impl<W> Builder<W>
 where
-    W: Write + Seek,
+    W: Write,
 {
     /// Begin constructing a new ELF file with the given header information
     /// in the given writer.
@@ -89,63 +137,255 @@ where
     /// The header information also serves to select which specific ELF variant
     /// the builder will generate, by choosing a [class](Class) and an
     /// [encoding](Encoding).
-    pub fn new(hdr: Header, mut target: W) -> Result<Self> {
-        let mut headmap = HeaderMap {
-            section_header_offset_field: 0,
-        };
-        match hdr.encoding {
-            Encoding::LSB => binbin::write_le(&mut target, |w| match hdr.class {
-                Class::ELF32 => {
-                    headmap = write_hdr_32(&hdr, w)?;
-                    Ok(())
-                }
-                Class::ELF64 => {
-                    headmap = write_hdr_64(&hdr, w)?;
-                    Ok(())
-                }
-            }),
-            Encoding::MSB => binbin::write_be(&mut target, |w| match hdr.class {
-                Class::ELF32 => {
-                    headmap = write_hdr_32(&hdr, w)?;
-                    Ok(())
-                }
-                Class::ELF64 => {
-                    headmap = write_hdr_64(&hdr, w)?;
-                    Ok(())
-                }
-            }),
-        }?;
-
-        let rodata_pos = target.stream_position()?;
-
+    ///
+    /// A new builder starts with a single data section named `.rodata`,
+    /// which is the [default section](Self::default_section).
+    pub fn new(hdr: Header, target: W) -> Result<Self> {
         Ok(Self {
             w: target,
             class: hdr.class,
             encoding: hdr.encoding,
-            headmap,
-            rodata_pos,
-            current_rodata_offset: 0,
+            machine: hdr.machine,
+            flags: hdr.flags,
+            sections: vec![SectionData {
+                name: None,
+                contents: SectionContents::Data(Vec::new()),
+                alignment: 1,
+                writable: false,
+                shared_data: false,
+            }],
+            zeroed_section: None,
+            companions: None,
             symbols: Vec::new(),
-            symbol_names: Vec::new(),
-            shstrtab: Cow::Borrowed(SHSTRTAB),
+            symbol_names: BTreeSet::new(),
+            require_c_identifiers: false,
+            cpp_names: None,
+            blob_index: None,
+            deduplicated_bytes: 0,
+            section_per_symbol: false,
+            symbol_section_template: None,
+            string_section: None,
+            relocations: Vec::new(),
         })
     }
 
+    /// Changes the name of the [default section](Self::default_section),
+    /// which is `.rodata` unless overridden, or `.data` if the section has
+    /// been made [writable](Self::set_section_writable).
     pub fn set_section_name(&mut self, name: impl AsRef<str>) {
-        let name = name.as_ref();
-
-        // If the caller is customizing the section name then we'll
-        // allocate a new buffer to represent our ".shstrtab" content,
-        // instead of using the default one in SHSTRTAB. However,
-        // we will still use SHSTRTAB as the starting point because
-        // we only actually want to replace the .rodata part, which
-        // is intentionally the last part so that we can just swap
-        // it out without interfering with any offsets into this section.
-        let mut shstrtab = Vec::<u8>::with_capacity(SHSTRTAB_RODATA as usize + name.len() + 1);
-        shstrtab.extend_from_slice(&SHSTRTAB[..SHSTRTAB_RODATA as usize]);
-        shstrtab.extend_from_slice(name.as_bytes());
-        shstrtab.push(0); // null terminator
-        self.shstrtab = Cow::Owned(shstrtab);
+        self.sections[0].name = Some(String::from(name.as_ref()));
+    }
+
+    /// Marks the given section as writable, so that its contents will be
+    /// placed in memory that the program can modify at runtime.
+    ///
+    /// Sections are read-only by default. A writable section is flagged with
+    /// both `SHF_WRITE` and `SHF_ALLOC`, and if it's the
+    /// [default section](Self::default_section) and its name hasn't been
+    /// overridden with [`set_section_name`](Self::set_section_name) then it
+    /// will be named `.data` instead of `.rodata`.
+    ///
+    /// This has no effect on [string sections](Self::add_string_section),
+    /// which are never writable because a linker may merge their contents
+    /// with identical strings from other object files, or on
+    /// [zeroed sections](Self::add_zeroed_section), which are always
+    /// writable.
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if the section
+    /// would become writable after [deduplication](Self::set_deduplicate)
+    /// has made some of its symbols share data, because modifying one of
+    /// them at runtime would then modify the others too.
+    pub fn set_section_writable(&mut self, section: Section, writable: bool) -> Result<()> {
+        self.check_section(section)?;
+        let sect = &mut self.sections[section.0];
+        if let SectionContents::Data(_) = sect.contents {
+            if writable && sect.shared_data {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a section can't be made writable after its symbols have been deduplicated",
+                ));
+            }
+            sect.writable = writable;
+        }
+        Ok(())
+    }
+
+    /// Returns the section that [`add_symbol`](Self::add_symbol) and
+    /// [`add_symbol_align`](Self::add_symbol_align) write into.
+    pub fn default_section(&self) -> Section {
+        Section(0)
+    }
+
+    /// Declares an additional data section with the given name, returning
+    /// a value that can be passed to [`add_symbol_in`](Self::add_symbol_in)
+    /// or [`add_symbol_align_in`](Self::add_symbol_align_in) to place symbols
+    /// into it.
+    ///
+    /// Sections appear in the output file in the order they were declared,
+    /// after the default section. A declared section is included in the
+    /// output even if no symbols are ever added to it.
+    ///
+    /// `add_section` doesn't check if you declare the same section name more
+    /// than once. ELF allows multiple sections with the same name, and a
+    /// linker will typically treat them as if they were a single section.
+    pub fn add_section(&mut self, name: impl Into<String>) -> Section {
+        let idx = self.sections.len();
+        self.sections.push(SectionData::new(name));
+        Section(idx)
+    }
+
+    /// Declares an additional section whose contents are all zero, returning
+    /// a value that can be passed to
+    /// [`add_zeroed_symbol_in`](Self::add_zeroed_symbol_in).
+    ///
+    /// A zeroed section is written as `SHT_NOBITS`, so it occupies no space
+    /// in the object file and is instead zero-initialized by the program
+    /// loader. It is always writable.
+    ///
+    /// Zeroed sections can only contain symbols added with
+    /// [`add_zeroed_symbol_in`](Self::add_zeroed_symbol_in), and those
+    /// symbols cannot be placed in any other kind of section.
+    pub fn add_zeroed_section(&mut self, name: impl Into<String>) -> Section {
+        let idx = self.sections.len();
+        self.sections.push(SectionData {
+            name: Some(name.into()),
+            contents: SectionContents::Zeroed(0),
+            alignment: 1,
+            writable: true,
+            shared_data: false,
+        });
+        Section(idx)
+    }
+
+    /// Returns the section that [`add_zeroed_symbol`](Self::add_zeroed_symbol)
+    /// writes into, which is named `.bss`.
+    ///
+    /// This section is created on first use, and so it only appears in the
+    /// output file if this function or `add_zeroed_symbol` is called.
+    pub fn default_zeroed_section(&mut self) -> Section {
+        match self.zeroed_section {
+            Some(section) => section,
+            None => {
+                let section = self.add_zeroed_section(".bss");
+                self.zeroed_section = Some(section);
+                section
+            }
+        }
+    }
+
+    /// Define a new symbol in the output file that refers to `size` bytes of
+    /// zero-initialized memory with the given alignment.
+    ///
+    /// Unlike [`add_symbol`](Self::add_symbol), this doesn't copy any data
+    /// into the output file. The symbol is placed in the
+    /// [default zeroed section](Self::default_zeroed_section), and so the
+    /// object file doesn't grow no matter how large the symbol is.
+    ///
+    /// The alignment must be a power of two, and the section's total size
+    /// must fit in the address size of the destination ELF file, or else
+    /// this returns an error of kind [`ErrorKind::InvalidInput`].
+    pub fn add_zeroed_symbol<S: Into<String>>(
+        &mut self,
+        name: S,
+        size: u64,
+        alignment: usize,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        self.add_zeroed_symbol_with(name, size, opts)
+    }
+
+    /// Like [`add_zeroed_symbol`](Self::add_zeroed_symbol), but places the
+    /// symbol in the given section, which must have been created with
+    /// [`add_zeroed_section`](Self::add_zeroed_section).
+    pub fn add_zeroed_symbol_in<S: Into<String>>(
+        &mut self,
+        section: Section,
+        name: S,
+        size: u64,
+        alignment: usize,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            section: Some(section),
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        self.add_zeroed_symbol_with(name, size, opts)
+    }
+
+    /// Like [`add_zeroed_symbol`](Self::add_zeroed_symbol), but with the
+    /// symbol's placement and attributes given as [`SymbolOptions`].
+    ///
+    /// If the options don't specify a section then the symbol is placed in
+    /// the [default zeroed section](Self::default_zeroed_section). If they
+    /// don't specify an alignment then the symbol is aligned to the word
+    /// size of the destination ELF file.
+    pub fn add_zeroed_symbol_with<S: Into<String>>(
+        &mut self,
+        name: S,
+        size: u64,
+        opts: SymbolOptions,
+    ) -> Result<Symbol> {
+        let name = name.into();
+        self.check_symbol_names(&name)?;
+        self.check_symbol_options(&opts)?;
+        check_element_size(size, self.element_size())?;
+        let alignment = opts.alignment.unwrap_or_else(|| self.default_alignment());
+        check_alignment(alignment)?;
+        // The size is checked before choosing a section so that a section
+        // created for this symbol won't be left behind if it's too large.
+        let class = self.class;
+        let max_size = self.word_mask();
+        if size > max_size {
+            return Err(zeroed_section_too_large(class));
+        }
+        let section = match opts.section {
+            Some(section) => section,
+            None if self.section_per_symbol => {
+                self.check_section_count()?;
+                self.add_zeroed_section(format!(".bss.{}", self.mangled_name(&name)))
+            }
+            None => self.default_zeroed_section(),
+        };
+        let sect = &mut self.sections[section.0];
+        let total = match &mut sect.contents {
+            SectionContents::Zeroed(total) => total,
+            SectionContents::Data(_) | SectionContents::Strings(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "zeroed symbols can only be added to a zeroed section",
+                ))
+            }
+        };
+        let offset = *total;
+
+        let pad_err = offset % alignment as u64;
+        let skip = if pad_err != 0 {
+            alignment as u64 - pad_err
+        } else {
+            0
+        };
+
+        let padded_size = size
+            .checked_add(skip)
+            .filter(|&padded_size| padded_size <= max_size - offset)
+            .ok_or_else(|| zeroed_section_too_large(class))?;
+        *total += padded_size;
+        if alignment > sect.alignment {
+            sect.alignment = alignment;
+        }
+
+        let sym = Symbol {
+            section,
+            rodata_offset: offset + skip,
+            size,
+            padded_size,
+            alignment,
+        };
+        self.push_symbol(name, sym, &opts);
+        Ok(sym)
     }
 
     /// Define a new symbol in the output file, using the contents of a given
@@ -154,18 +394,13 @@ where
     /// `add_symbol` will read the given reader to completion and copy all of
     /// its data into the output file.
     ///
-    /// `add_symbol` doesn't check if you define the same symbol name more than
-    /// once, but doing so will create a confusing object file that may not
-    /// be accepted by an ELF linker.
+    /// `add_symbol` returns an error wrapping a [`SymbolNameError`] if the
+    /// given name is empty, contains a null byte, or was already defined.
     ///
     /// This function aligns the data to the word size of the destination ELF
     /// file. Use `add_symbol_align` instead if you need specific alignment.
     pub fn add_symbol<S: Into<String>, R: Read>(&mut self, name: S, src: R) -> Result<Symbol> {
-        let align = match self.class {
-            Class::ELF32 => 4,
-            Class::ELF64 => 8,
-        };
-        self.add_symbol_align(name, align, src)
+        self.add_symbol_with(name, SymbolOptions::default(), src)
     }
 
     /// Define a new symbol in the output file with a particular alignment,
@@ -174,402 +409,1158 @@ where
     /// `add_symbol_align` will read the given reader to completion and copy
     /// all of its data into the output file.
     ///
-    /// `add_symbol_align` doesn't check if you define the same symbol name
-    /// more than once, but doing so will create a confusing object file that
-    /// may not be accepted by an ELF linker.
+    /// `add_symbol_align` returns an error wrapping a [`SymbolNameError`] if
+    /// the given name is empty, contains a null byte, or was already defined,
+    /// and an error of kind [`ErrorKind::InvalidInput`] if the alignment
+    /// isn't a power of two.
     pub fn add_symbol_align<S: Into<String>, R: Read>(
         &mut self,
         name: S,
         alignment: usize,
         src: R,
     ) -> Result<Symbol> {
-        let offset = self.current_rodata_offset;
+        let opts = SymbolOptions {
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        self.add_symbol_with(name, opts, src)
+    }
 
-        let pad_err = offset % alignment as u64;
-        let mut skip = 0;
-        if pad_err != 0 {
-            for _ in pad_err..(alignment as u64) {
-                self.w.write_all(&b" "[..])?;
-                skip += 1;
+    /// Like [`add_symbol`](Self::add_symbol), but places the symbol in the
+    /// given section instead of the default section.
+    pub fn add_symbol_in<S: Into<String>, R: Read>(
+        &mut self,
+        section: Section,
+        name: S,
+        src: R,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            section: Some(section),
+            ..SymbolOptions::default()
+        };
+        self.add_symbol_with(name, opts, src)
+    }
+
+    /// Like [`add_symbol_align`](Self::add_symbol_align), but places the
+    /// symbol in the given section instead of the default section.
+    ///
+    /// The given section must not be a
+    /// [zeroed section](Self::add_zeroed_section).
+    pub fn add_symbol_align_in<S: Into<String>, R: Read>(
+        &mut self,
+        section: Section,
+        name: S,
+        alignment: usize,
+        src: R,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            section: Some(section),
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        self.add_symbol_with(name, opts, src)
+    }
+
+    /// Like [`add_symbol`](Self::add_symbol), but with the symbol's placement
+    /// and attributes given as [`SymbolOptions`].
+    ///
+    /// If the options don't specify a section then the symbol is placed in
+    /// the [default section](Self::default_section). If they don't specify
+    /// an alignment then the symbol is aligned to the word size of the
+    /// destination ELF file.
+    pub fn add_symbol_with<S: Into<String>, R: Read>(
+        &mut self,
+        name: S,
+        opts: SymbolOptions,
+        src: R,
+    ) -> Result<Symbol> {
+        let name = name.into();
+        let alignment = self.check_data_symbol(&name, &opts)?;
+        self.add_data_symbol(name, alignment, &opts, src, true)
+    }
+
+    /// Checks the name and options given to
+    /// [`add_symbol_with`](Self::add_symbol_with), returning the alignment
+    /// for the symbol.
+    fn check_data_symbol(&self, name: &str, opts: &SymbolOptions) -> Result<usize> {
+        self.check_symbol_names(name)?;
+        self.check_symbol_options(opts)?;
+        let alignment = opts.alignment.unwrap_or_else(|| self.default_alignment());
+        check_alignment(alignment)?;
+        Ok(alignment)
+    }
+
+    /// Define a new symbol whose contents are an embedded [`Filesystem`],
+    /// including its table of contents.
+    ///
+    /// See the [`vfs`] module for details on the layout of the symbol's data.
+    pub fn add_filesystem<S: Into<String>>(&mut self, name: S, fs: &Filesystem) -> Result<Symbol> {
+        self.add_filesystem_with(name, SymbolOptions::default(), fs)
+    }
+
+    /// Like [`add_filesystem`](Self::add_filesystem), but with the symbol's
+    /// placement and attributes given as [`SymbolOptions`].
+    ///
+    /// If the options specify an alignment, it must be a power of two and a
+    /// multiple of [`vfs::FILE_ALIGNMENT`] so that the file contents will be
+    /// aligned.
+    pub fn add_filesystem_with<S: Into<String>>(
+        &mut self,
+        name: S,
+        opts: SymbolOptions,
+        fs: &Filesystem,
+    ) -> Result<Symbol> {
+        let name = name.into();
+        self.check_symbol_names(&name)?;
+        self.check_symbol_options(&opts)?;
+        let alignment = opts.alignment.unwrap_or(vfs::FILE_ALIGNMENT);
+        check_alignment(alignment)?;
+        if alignment % vfs::FILE_ALIGNMENT != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "filesystem must be aligned to a multiple of vfs::FILE_ALIGNMENT",
+            ));
+        }
+        let data = fs.encode(self.encoding)?;
+        self.add_data_symbol(name, alignment, &opts, &data[..], true)
+    }
+
+    /// Define a new symbol whose contents are given as [`RelocatableData`],
+    /// which can include pointers to other symbols that the linker will fill
+    /// in.
+    ///
+    /// The target of each pointer is given by symbol name. If there's no
+    /// symbol of that name in this builder when it's closed then the output
+    /// will refer to an undefined symbol of that name, to be provided by
+    /// some other object file.
+    ///
+    /// Pointers require relocation entries, which are specific to the target
+    /// machine. `add_relocatable_symbol` supports the x86 (`386`), x86-64,
+    /// ARM, AArch64 and RISC-V architectures, and returns an error of kind
+    /// [`ErrorKind::Unsupported`] if the data contains pointers and the
+    /// [header](Header) specified any other machine.
+    ///
+    /// The data is aligned to the word size of the destination ELF file.
+    /// If the program will be linked as position-independent code then you
+    /// may wish to place the symbol in a writable section, or a section named
+    /// `.data.rel.ro`, so that the dynamic linker can apply relocations.
+    pub fn add_relocatable_symbol<S: Into<String>>(
+        &mut self,
+        name: S,
+        data: &RelocatableData,
+    ) -> Result<Symbol> {
+        self.add_relocatable_symbol_with(name, SymbolOptions::default(), data)
+    }
+
+    /// Like [`add_relocatable_symbol`](Self::add_relocatable_symbol), but
+    /// with the symbol's placement and attributes given as
+    /// [`SymbolOptions`].
+    ///
+    /// If the options specify an alignment, it must be at least the word size
+    /// of the destination ELF file so that the pointers will be aligned.
+    pub fn add_relocatable_symbol_with<S: Into<String>>(
+        &mut self,
+        name: S,
+        opts: SymbolOptions,
+        data: &RelocatableData,
+    ) -> Result<Symbol> {
+        let name = name.into();
+        self.check_symbol_names(&name)?;
+        self.check_symbol_options(&opts)?;
+        let word_size = self.word_size();
+        let alignment = opts.alignment.unwrap_or(word_size);
+        check_alignment(alignment)?;
+        if alignment % word_size != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "relocatable data must be aligned to at least the word size",
+            ));
+        }
+        let reloc_kind = if data.has_pointers() {
+            Some(pointer_relocation(self.machine, self.class)?)
+        } else {
+            None
+        };
+
+        // We'll encode the data before choosing a section, so that we won't
+        // create a new section if the data is invalid.
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut relocs: Vec<(u64, String, i64)> = Vec::new();
+        for field in data.fields.iter() {
+            match field {
+                Field::Bytes(v) => bytes.extend_from_slice(v),
+                Field::Word(v) => self.encode_word(*v, &mut bytes)?,
+                Field::Pointer { target, addend } => {
+                    let target = self.check_name_syntax(target)?;
+                    if self.class == Class::ELF32 && *addend != *addend as i32 as i64 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "pointer addend must fit in 32 bits for ELF32",
+                        ));
+                    }
+                    relocs.push((bytes.len() as u64, target, *addend));
+                    match reloc_kind {
+                        // With REL-style relocations the addend is stored
+                        // in the field itself, and otherwise it's in the
+                        // relocation entry and the field is zero.
+                        Some(RelocationKind { rela: false, .. }) => {
+                            self.encode_word(*addend as u64 & self.word_mask(), &mut bytes)?
+                        }
+                        _ => self.encode_word(0, &mut bytes)?,
+                    }
+                }
             }
         }
 
-        let encoding = self.encoding;
-        let class = self.class;
-        let length = match encoding {
-            Encoding::LSB => binbin::write_le(&mut self.w, |w| match class {
-                Class::ELF32 => write_symbol_data(src, w),
-                Class::ELF64 => write_symbol_data(src, w),
-            }),
-            Encoding::MSB => binbin::write_be(&mut self.w, |w| match class {
-                Class::ELF32 => write_symbol_data(src, w),
-                Class::ELF64 => write_symbol_data(src, w),
-            }),
-        }?;
-        let padded_size = length + skip;
+        // Relocated data must not be deduplicated, because data that looks
+        // identical before relocation might not be identical afterwards.
+        let sym = self.add_data_symbol(name, alignment, &opts, &bytes[..], false)?;
+        if let Some(kind) = reloc_kind {
+            for (offset, target, addend) in relocs {
+                self.relocations.push(Relocation {
+                    section: sym.section,
+                    offset: sym.rodata_offset + offset,
+                    target,
+                    kind,
+                    addend,
+                });
+            }
+        }
+        Ok(sym)
+    }
 
-        let sym = Symbol {
-            rodata_offset: offset + skip,
-            size: length,
-            padded_size,
+    /// Adds a data symbol in the section chosen by
+    /// [`data_section_for`](Self::data_section_for), reading its data from
+    /// the given reader.
+    fn add_data_symbol<R: Read>(
+        &mut self,
+        name: String,
+        alignment: usize,
+        opts: &SymbolOptions,
+        mut src: R,
+        deduplicate: bool,
+    ) -> Result<Symbol> {
+        let mut pending = self.begin_data_symbol(&name, alignment, opts)?;
+        let result = src.read_to_end(pending.data()).map(|_| ());
+        pending.end(name, opts, result, deduplicate)
+    }
+
+    /// Like [`begin_data_symbol_in`](Self::begin_data_symbol_in), but in the
+    /// section chosen by [`data_section_for`](Self::data_section_for). If
+    /// that section is created for this symbol and the symbol isn't added,
+    /// then the section is removed again.
+    fn begin_data_symbol(
+        &mut self,
+        name: &str,
+        alignment: usize,
+        opts: &SymbolOptions,
+    ) -> Result<PendingData<'_, W>> {
+        let section_count = self.sections.len();
+        let section = self.data_section_for(name, opts)?;
+        let mut pending = self.begin_data_symbol_in(section, alignment)?;
+        pending.section_count = section_count;
+        Ok(pending)
+    }
+
+    /// Returns the section where a data symbol of the given name should be
+    /// placed, creating a new section if necessary.
+    fn data_section_for(&mut self, name: &str, opts: &SymbolOptions) -> Result<Section> {
+        let section = match opts.section {
+            Some(section) => section,
+            None if self.section_per_symbol => {
+                self.check_section_count()?;
+                let default = &self.sections[0];
+                let mangled = self.mangled_name(name);
+                let section_name = match &self.symbol_section_template {
+                    Some(template) => template.replacen("{}", &mangled, 1),
+                    None => format!("{}.{}", default.name(), mangled),
+                };
+                let writable = default.writable;
+                let section = self.add_section(section_name);
+                self.set_section_writable(section, writable)?;
+                section
+            }
+            None => self.default_section(),
+        };
+        if let SectionContents::Strings(_) = self.sections[section.0].contents {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "a string section can only contain string symbols",
+            ));
+        }
+        Ok(section)
+    }
+
+    /// Pads the given section to the given alignment, ready for the data of
+    /// a new symbol to be appended to [`PendingData::data`].
+    ///
+    /// The padding and data are discarded again unless the symbol is then
+    /// defined by [`PendingData::end`].
+    fn begin_data_symbol_in(
+        &mut self,
+        section: Section,
+        alignment: usize,
+    ) -> Result<PendingData<'_, W>> {
+        let section_count = self.sections.len();
+        let data = match &mut self.sections[section.0].contents {
+            SectionContents::Data(data) | SectionContents::Strings(data) => data,
+            SectionContents::Zeroed(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a zeroed section cannot contain symbols with data",
+                ))
+            }
+        };
+        let offset = data.len() as u64;
+        let start = offset.next_multiple_of(alignment as u64);
+        data.resize(start as usize, b' ');
+        Ok(PendingData {
+            builder: self,
+            section,
             alignment,
+            offset,
+            section_count,
+            defined: false,
+        })
+    }
+
+    /// Enables or disables placing each symbol added after this call in its
+    /// own section, to allow a linker to discard the data for any symbols
+    /// that the program doesn't refer to.
+    ///
+    /// This is similar to the `-fdata-sections` option in C compilers. Each
+    /// symbol that isn't explicitly placed in a particular section gets a new
+    /// section whose name is the name of the section it would otherwise have
+    /// been placed in, followed by a period and the symbol name: for example,
+    /// `.rodata.logo` or `.bss.framebuffer`. The new section is writable if
+    /// the default section is writable.
+    ///
+    /// For a linker to discard unused symbols, it must be configured to do
+    /// so, such as with the `--gc-sections` option for GNU ld.
+    ///
+    /// An ELF file can have at most 65279 sections, including the sections
+    /// elfbin adds for its own metadata. Once a symbol would need a section
+    /// beyond that, the `add_symbol` functions return an error of kind
+    /// [`ErrorKind::InvalidInput`].
+    pub fn set_section_per_symbol(&mut self, enabled: bool) {
+        self.section_per_symbol = enabled;
+    }
+
+    /// Changes how the sections created by
+    /// [`set_section_per_symbol`](Self::set_section_per_symbol) for data
+    /// symbols are named, using a template where the first `{}` is replaced
+    /// by the symbol name: for example, `.rodata.assets.{}`.
+    ///
+    /// If the template is `None`, which is the default, the sections are
+    /// named after the [default section](Self::default_section). The sections
+    /// for zeroed symbols are always named `.bss.` followed by the symbol
+    /// name.
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if the template
+    /// doesn't contain `{}`, because every symbol's section would then have
+    /// the same name.
+    pub fn set_symbol_section_template(&mut self, template: Option<String>) -> Result<()> {
+        if let Some(template) = &template {
+            if !template.contains("{}") {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "section name template must contain {} for the symbol name",
+                ));
+            }
+        }
+        self.symbol_section_template = template;
+        Ok(())
+    }
+
+    /// Returns an error if adding another section would leave no room in the
+    /// section header table for the metadata sections.
+    fn check_section_count(&self) -> Result<()> {
+        // Counting the new section, .strtab and .symtab, the number of
+        // sections must be below the reserved range of section indices.
+        if FIRST_DATA_SECTION_IDX as usize + self.sections.len() + 3 >= SHN_LORESERVE as usize {
+            return Err(too_many_sections());
+        }
+        Ok(())
+    }
+
+    /// Enables or disables deduplication of symbol data added after this call.
+    ///
+    /// When enabled, the `add_symbol` functions compare incoming data with
+    /// the data of earlier symbols in the same section, and if an identical
+    /// copy with suitable alignment exists then the new symbol refers to
+    /// that copy instead of writing the data again. Use
+    /// [`deduplicated_bytes`](Self::deduplicated_bytes) to find out how much
+    /// space this saved.
+    ///
+    /// Data in [writable](Self::set_section_writable) sections is never
+    /// deduplicated, because modifying one symbol's data at runtime would
+    /// then unexpectedly modify the others too. For the same reason, a
+    /// section can't be made writable once some of its data is shared.
+    pub fn set_deduplicate(&mut self, deduplicate: bool) {
+        if !deduplicate {
+            self.blob_index = None;
+        } else if self.blob_index.is_none() {
+            self.blob_index = Some(BTreeMap::new());
+        }
+    }
+
+    /// Returns the total number of bytes of symbol data that were not
+    /// written because of [deduplication](Self::set_deduplicate).
+    pub fn deduplicated_bytes(&self) -> u64 {
+        self.deduplicated_bytes
+    }
+
+    /// Declares an additional section for NUL-terminated strings that a
+    /// linker may merge with identical strings from other object files,
+    /// returning a value that can be passed to
+    /// [`add_string_symbol_in`](Self::add_string_symbol_in).
+    ///
+    /// A string section is flagged with `SHF_MERGE` and `SHF_STRINGS`, and
+    /// can only contain symbols added with the `add_string_symbol` functions.
+    pub fn add_string_section(&mut self, name: impl Into<String>) -> Section {
+        let idx = self.sections.len();
+        self.sections.push(SectionData {
+            name: Some(name.into()),
+            contents: SectionContents::Strings(Vec::new()),
+            alignment: 1,
+            writable: false,
+            shared_data: false,
+        });
+        Section(idx)
+    }
+
+    /// Returns the section that [`add_string_symbol`](Self::add_string_symbol)
+    /// writes into, which is named `.rodata.str1.1` as is conventional for
+    /// merged strings with one-byte characters.
+    ///
+    /// This section is created on first use, and so it only appears in the
+    /// output file if this function or `add_string_symbol` is called.
+    pub fn default_string_section(&mut self) -> Section {
+        match self.string_section {
+            Some(section) => section,
+            None => {
+                let section = self.add_string_section(".rodata.str1.1");
+                self.string_section = Some(section);
+                section
+            }
+        }
+    }
+
+    /// Define a new symbol referring to the given string, followed by a NUL
+    /// terminator, in the [default string section](Self::default_string_section).
+    ///
+    /// The size of the symbol includes the NUL terminator. The string itself
+    /// must not contain any NUL characters, because the linker would then
+    /// treat it as two separate strings when merging.
+    ///
+    /// Because a linker may merge identical strings, a program must not
+    /// assume that two string symbols have different addresses.
+    pub fn add_string_symbol<S: Into<String>>(&mut self, name: S, value: &str) -> Result<Symbol> {
+        self.add_string_symbol_with(name, SymbolOptions::default(), value)
+    }
+
+    /// Like [`add_string_symbol`](Self::add_string_symbol), but places the
+    /// symbol in the given section, which must have been created with
+    /// [`add_string_section`](Self::add_string_section).
+    pub fn add_string_symbol_in<S: Into<String>>(
+        &mut self,
+        section: Section,
+        name: S,
+        value: &str,
+    ) -> Result<Symbol> {
+        let opts = SymbolOptions {
+            section: Some(section),
+            ..SymbolOptions::default()
         };
-        self.symbols.push(sym);
-        self.symbol_names.push(name.into());
-        self.current_rodata_offset += padded_size;
-        Ok(sym)
+        self.add_string_symbol_with(name, opts, value)
     }
 
-    /// Finalizes the ELF metadata in the underlying file and then returns
-    /// that file.
+    /// Like [`add_string_symbol`](Self::add_string_symbol), but with the
+    /// symbol's placement and attributes given as [`SymbolOptions`].
     ///
-    /// If you don't call `close` then the file will be left in a state where
-    /// it contains any symbol data written previously but it lacks the
-    /// necessary metadata for an ELF linker to find that data, and thus the
-    /// object file will appear to have no symbols at all.
-    pub fn close(mut self) -> Result<W> {
-        let encoding = self.encoding;
-        let class = self.class;
-        let sym_names = self.symbol_names;
-        let syms = self.symbols;
-        let rodata_pos = self.rodata_pos;
-        let shstrtab = &self.shstrtab;
+    /// If the options don't specify a section then the symbol is placed in
+    /// the [default string section](Self::default_string_section). The
+    /// alignment in the options is ignored, because strings in a mergeable
+    /// string section are never padded.
+    pub fn add_string_symbol_with<S: Into<String>>(
+        &mut self,
+        name: S,
+        opts: SymbolOptions,
+        value: &str,
+    ) -> Result<Symbol> {
+        let name = name.into();
+        self.check_symbol_names(&name)?;
+        self.check_symbol_options(&opts)?;
+        if value.contains('\0') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "string symbol value must not contain NUL characters",
+            ));
+        }
+        let section = match opts.section {
+            Some(section) => section,
+            None => self.default_string_section(),
+        };
+        if let SectionContents::Strings(_) = self.sections[section.0].contents {
+            let mut pending = self.begin_data_symbol_in(section, 1)?;
+            let data = pending.data();
+            data.extend_from_slice(value.as_bytes());
+            data.push(0); // null terminator
+            pending.end(name, &opts, Ok(()), true)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "string symbols can only be added to a string section",
+            ))
+        }
+    }
 
-        let map = match encoding {
-            Encoding::LSB => binbin::write_le(&mut self.w, |w| match class {
-                Class::ELF32 => {
-                    write_metadata_sections_32(rodata_pos, &sym_names, &syms, shstrtab, w)
-                }
-                Class::ELF64 => {
-                    write_metadata_sections_64(rodata_pos, &sym_names, &syms, shstrtab, w)
-                }
-            }),
-            Encoding::MSB => binbin::write_be(&mut self.w, |w| match class {
-                Class::ELF32 => {
-                    write_metadata_sections_32(rodata_pos, &sym_names, &syms, shstrtab, w)
-                }
-                Class::ELF64 => {
-                    write_metadata_sections_64(rodata_pos, &sym_names, &syms, shstrtab, w)
-                }
-            }),
-        }?;
-
-        let final_pos = self.w.stream_position()?;
-        self.w.seek(std::io::SeekFrom::Start(
-            self.headmap.section_header_offset_field,
-        ))?;
-        let section_header_pos = map.section_header_offset;
-        match encoding {
-            Encoding::LSB => binbin::write_le(&mut self.w, |w| match class {
-                Class::ELF32 => w.write(section_header_pos as u32).map(|_| ()),
-                Class::ELF64 => w.write(section_header_pos as u64).map(|_| ()),
-            }),
-            Encoding::MSB => binbin::write_be(&mut self.w, |w| match class {
-                Class::ELF32 => w.write(section_header_pos as u32).map(|_| ()),
-                Class::ELF64 => w.write(section_header_pos as u64).map(|_| ()),
-            }),
-        }?;
-        self.w.seek(std::io::SeekFrom::Start(final_pos))?;
+    /// Define an additional name for a symbol returned by an earlier call to
+    /// one of the `add_symbol` functions, without duplicating its data.
+    ///
+    /// The alias has the same value and size as the given symbol, and is
+    /// global with default visibility. Use
+    /// [`add_symbol_alias_with`](Self::add_symbol_alias_with) to choose a
+    /// different binding or visibility. If
+    /// [companion symbols](Self::set_companion_symbols) are enabled then the
+    /// alias gets its own set of companions.
+    pub fn add_symbol_alias<S: Into<String>>(&mut self, target: Symbol, name: S) -> Result<Symbol> {
+        self.add_symbol_alias_with(target, name, SymbolOptions::default())
+    }
+
+    /// Like [`add_symbol_alias`](Self::add_symbol_alias), but with the
+    /// alias's binding and visibility given as [`SymbolOptions`].
+    ///
+    /// The section and alignment in the options are ignored, because an
+    /// alias always refers to the same data as its target.
+    pub fn add_symbol_alias_with<S: Into<String>>(
+        &mut self,
+        target: Symbol,
+        name: S,
+        opts: SymbolOptions,
+    ) -> Result<Symbol> {
+        self.check_section(target.section)?;
+        let name = name.into();
+        self.check_symbol_names(&name)?;
+        check_options(&opts)?;
+        self.push_symbol(name, target, &opts);
+        Ok(target)
+    }
+
+    /// Define a new absolute symbol, whose value is the given number rather
+    /// than the address of some data.
+    ///
+    /// Absolute symbols don't occupy any space in the output, and so are
+    /// useful for link-time constants that a program reads by taking the
+    /// address of the symbol, such as `(uintptr_t)&ASSET_COUNT` in C.
+    ///
+    /// If `size` is `None` then the symbol has size zero. The value must fit
+    /// in the address size of the destination ELF file.
+    pub fn add_absolute_symbol<S: Into<String>>(
+        &mut self,
+        name: S,
+        value: u64,
+        size: Option<u64>,
+    ) -> Result<()> {
+        let name = name.into();
+        self.check_symbol_name(&name)?;
+        let size = size.unwrap_or(0);
+        if self.class == Class::ELF32 && (value > u32::MAX as u64 || size > u32::MAX as u64) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "absolute symbol value and size must fit in 32 bits for ELF32",
+            ));
+        }
+        self.push_entry(SymbolEntry {
+            name,
+            value,
+            size,
+            section_idx: SHN_ABS,
+            alignment: 1,
+            typ: STT_NOTYPE,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        });
+        Ok(())
+    }
+
+    /// Requires all symbol names added after this call to be valid C
+    /// identifiers, so that they can be declared directly in C source code.
+    ///
+    /// When enabled, the `add_symbol` functions return an error wrapping
+    /// [`SymbolNameError::NotCIdentifier`] for any name that doesn't consist
+    /// only of ASCII letters, digits and underscores, or that starts with a
+    /// digit. This applies to the names of companion symbols too.
+    pub fn set_require_c_identifiers(&mut self, require: bool) {
+        self.require_c_identifiers = require;
+    }
+
+    /// Enables or disables the generation of companion symbols for each
+    /// symbol added after this call, in the style of `objcopy -I binary`.
+    ///
+    /// When enabled, each symbol's own name is derived from the
+    /// [`start`](CompanionNaming::start) template, and two additional symbols
+    /// are defined alongside it: one whose value is the address just after
+    /// the end of the data, and an absolute symbol whose value is the size
+    /// of the data. The companion symbols have the same binding and
+    /// visibility as the symbol they accompany.
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if any of the
+    /// templates doesn't contain `{}`, because every symbol would then have
+    /// a companion of the same name.
+    pub fn set_companion_symbols(&mut self, naming: Option<CompanionNaming>) -> Result<()> {
+        if let Some(naming) = &naming {
+            if !naming.is_valid() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "companion symbol name templates must contain {} for the symbol name",
+                ));
+            }
+        }
+        self.companions = naming;
+        Ok(())
+    }
+
+    fn push_symbol(&mut self, name: String, sym: Symbol, opts: &SymbolOptions) {
+        let section_idx = sym.section.header_idx();
+        let mut entry = SymbolEntry {
+            name,
+            value: sym.rodata_offset,
+            size: sym.size,
+            section_idx,
+            alignment: sym.alignment as u64,
+            typ: STT_OBJECT,
+            binding: opts.binding,
+            visibility: opts.visibility,
+        };
+        let companions = match &self.companions {
+            Some(naming) => naming,
+            None => {
+                self.push_entry(entry);
+                return;
+            }
+        };
+
+        let end = SymbolEntry {
+            name: companions.end_name(&entry.name),
+            value: sym.rodata_offset + sym.size,
+            size: 0,
+            section_idx,
+            alignment: 1,
+            typ: STT_NOTYPE,
+            binding: opts.binding,
+            visibility: opts.visibility,
+        };
+        let size = SymbolEntry {
+            name: companions.size_name(&entry.name),
+            value: sym.size,
+            size: 0,
+            section_idx: SHN_ABS,
+            alignment: 1,
+            typ: STT_NOTYPE,
+            binding: opts.binding,
+            visibility: opts.visibility,
+        };
+        entry.name = companions.start_name(&entry.name);
+        self.push_entry(entry);
+        self.push_entry(end);
+        self.push_entry(size);
+    }
 
+    fn push_entry(&mut self, mut entry: SymbolEntry) {
+        entry.name = self.mangled_name(&entry.name);
+        self.symbol_names.insert(entry.name.clone());
+        self.symbols.push(entry);
+    }
+
+    /// Checks that the given options can be written to the symbol table, and
+    /// that the section they specify, if any, belongs to this builder.
+    fn check_symbol_options(&self, opts: &SymbolOptions) -> Result<()> {
+        check_options(opts)?;
+        match opts.section {
+            Some(section) => self.check_section(section),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns an error if the given section wasn't returned by this
+    /// builder.
+    fn check_section(&self, section: Section) -> Result<()> {
+        if section.0 >= self.sections.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "section does not belong to this builder",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the given name, and the names of any companion symbols
+    /// that would be generated for it, can be added to the symbol table.
+    fn check_symbol_names(&self, name: &str) -> Result<()> {
+        let companions = match &self.companions {
+            Some(naming) => naming,
+            None => return self.check_symbol_name(name),
+        };
+        if name.is_empty() {
+            return Err(SymbolNameError::Empty.into());
+        }
+        let names = [
+            companions.start_name(name),
+            companions.end_name(name),
+            companions.size_name(name),
+        ];
+        for (i, name) in names.iter().enumerate() {
+            self.check_symbol_name(name)?;
+            if names[..i].contains(name) {
+                return Err(SymbolNameError::Duplicate(name.clone()).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the given name can be added to the symbol table.
+    fn check_symbol_name(&self, name: &str) -> Result<()> {
+        let name = self.check_name_syntax(name)?;
+        if self.symbol_names.contains(&name) {
+            return Err(SymbolNameError::Duplicate(name).into());
+        }
+        Ok(())
+    }
+
+    /// Checks that the given name is acceptable in the symbol table, whether
+    /// or not a symbol of that name is already defined, and returns the name
+    /// as it will appear there.
+    fn check_name_syntax(&self, name: &str) -> Result<String> {
+        if name.is_empty() {
+            return Err(SymbolNameError::Empty.into());
+        }
+        if name.contains('\0') {
+            return Err(SymbolNameError::ContainsNul(String::from(name)).into());
+        }
+        if self.cpp_names.is_some() && !is_cpp_name(name) {
+            return Err(SymbolNameError::NotCppName(String::from(name)).into());
+        }
+        let name = self.mangled_name(name);
+        if self.require_c_identifiers && !is_c_identifier(&name) {
+            return Err(SymbolNameError::NotCIdentifier(name).into());
+        }
+        Ok(name)
+    }
+
+    /// Appends the given value to `into` as a word in the encoding and size
+    /// of the destination ELF file.
+    fn encode_word(&self, v: u64, into: &mut Vec<u8>) -> Result<()> {
+        let size = self.word_size();
+        if v & !self.word_mask() != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "word value must fit in 32 bits for ELF32",
+            ));
+        }
+        let bytes = match self.encoding {
+            Encoding::LSB => v.to_le_bytes(),
+            Encoding::MSB => v.to_be_bytes(),
+        };
+        match self.encoding {
+            Encoding::LSB => into.extend_from_slice(&bytes[..size]),
+            Encoding::MSB => into.extend_from_slice(&bytes[8 - size..]),
+        }
+        Ok(())
+    }
+
+    /// Returns a mask of the bits that fit in a word of the destination ELF
+    /// file.
+    fn word_mask(&self) -> u64 {
+        match self.class {
+            Class::ELF32 => u32::MAX as u64,
+            Class::ELF64 => u64::MAX,
+        }
+    }
+
+    /// Returns the default alignment for symbols, which is the word size of
+    /// the destination ELF file.
+    fn word_size(&self) -> usize {
+        match self.class {
+            Class::ELF32 => 4,
+            Class::ELF64 => 8,
+        }
+    }
+
+    /// Writes the complete ELF file to the underlying writer and then
+    /// returns that writer.
+    ///
+    /// The file is buffered in memory until `close` is called, so if you
+    /// don't call `close` then nothing will be written at all. The section
+    /// contents are written directly from those buffers, without first
+    /// assembling a second copy of the whole file.
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if the builder
+    /// has more sections than an ELF file can have.
+    pub fn close(mut self) -> Result<W> {
+        let layout = self.layout()?;
+        write_file(&layout, &self.sections, &mut self.w)?;
         self.w.flush()?;
         Ok(self.w)
     }
-}
 
-fn write_hdr_32<'a, W: Write + Seek, E: Endian>(
-    hdr: &Header,
-    w: &mut binbin::Writer<'a, W, E>,
-) -> Result<HeaderMap> {
-    write_ident(hdr, w)?;
-    w.write(ET_REL)?;
-    w.write(hdr.machine)?;
-    w.write(1_u32)?; // header version
-    w.write(0_u32)?; // entry point (none)
-    w.write(0_u32)?; // no program headers
-    let shoff_pos = w.position()?;
-    w.write(0_u32)?; // placeholder for section header offset
-    w.write(hdr.flags)?;
-    let header_size_dfr = w.write_deferred(0_u16)?;
-    w.write(0_u16)?; // no program header entries
-    w.write(0_u16)?; // no program header entries
-    w.write(40_u16)?; // section header entry size
-    w.write(5_u16)?; // section header entry count
-    w.write(1_u16)?; // section names are in section 1
-
-    let pos = w.position()? as u16;
-    w.resolve(header_size_dfr, pos)?;
-
-    w.align(4)?;
-
-    Ok(HeaderMap {
-        section_header_offset_field: shoff_pos,
-    })
+    /// Like [`close`](Self::close), but first parses the generated file to
+    /// check that it's well-formed and that it contains exactly the sections
+    /// and symbols that were added to the builder.
+    ///
+    /// Among other things this checks that every section lies within the
+    /// file at an offset that honors its alignment, that every string table
+    /// index is valid, that `e_shstrndx` refers to the section name table,
+    /// and that every symbol lies within its section at an offset that
+    /// honors the symbol's alignment.
+    ///
+    /// If a check fails then this returns an error of kind
+    /// [`ErrorKind::InvalidData`] describing the problem, and nothing is
+    /// written to the underlying writer. Such an error always indicates a
+    /// bug in elfbin.
+    ///
+    /// Unlike `close`, this assembles a complete copy of the file in memory
+    /// so that it can be checked before anything is written.
+    pub fn close_verified(mut self) -> Result<W> {
+        let buf = self.file_bytes()?;
+        self.verify(&buf)?;
+        self.w.write_all(&buf)?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+
+    fn header(&self) -> Header {
+        Header {
+            class: self.class,
+            encoding: self.encoding,
+            machine: self.machine,
+            flags: self.flags,
+        }
+    }
+
+    fn layout(&self) -> Result<FileLayout> {
+        // Each section with relocations has a relocation section too, and
+        // the number of sections must be below the reserved range of section
+        // indices.
+        let reloc_sections: BTreeSet<Section> =
+            self.relocations.iter().map(|reloc| reloc.section).collect();
+        let count =
+            FIRST_DATA_SECTION_IDX as usize + self.sections.len() + 2 + reloc_sections.len();
+        if count >= SHN_LORESERVE as usize {
+            return Err(too_many_sections());
+        }
+        Ok(layout_file(
+            &self.header(),
+            &self.sections,
+            &self.symbols,
+            &self.relocations,
+        ))
+    }
+
+    fn file_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        write_file(&self.layout()?, &self.sections, &mut buf)?;
+        Ok(buf)
+    }
 }
 
-fn write_hdr_64<'a, W: Write + Seek, E: Endian>(
-    hdr: &Header,
-    w: &mut binbin::Writer<'a, W, E>,
-) -> Result<HeaderMap> {
-    write_ident(hdr, w)?;
-    w.write(ET_REL)?;
-    w.write(hdr.machine)?;
-    w.write(1_u32)?; // header version
-    w.write(0_u64)?; // entry point (none)
-    w.write(0_u64)?; // no program headers
-    let shoff_pos = w.position()?;
-    w.write(0_u64)?; // placeholder for section header offset
-    w.write(hdr.flags)?;
-    let header_size_dfr = w.write_deferred(0_u16)?;
-    w.write(0_u16)?; // no program header entries
-    w.write(0_u16)?; // no program header entries
-    w.write(64_u16)?; // section header entry size
-    w.write(5_u16)?; // section header entry count
-    w.write(1_u16)?; // section names are in section 1
-
-    let pos = w.position()? as u16;
-    w.resolve(header_size_dfr, pos)?;
-
-    w.align(8)?;
-
-    Ok(HeaderMap {
-        section_header_offset_field: shoff_pos,
-    })
+/// The bytes of an ELF file other than the contents of the data sections,
+/// which are written from the builder's buffers without being copied here.
+struct FileLayout {
+    /// The ELF header, which is at the start of the file.
+    header: Vec<u8>,
+    /// The position in the file of each data section.
+    section_pos: Vec<u64>,
+    /// The position in the file of the bytes in `trailer`.
+    trailer_pos: u64,
+    /// Everything after the data sections: the metadata sections and then
+    /// the section header table.
+    trailer: Vec<u8>,
 }
 
-fn write_symbol_data<R: Read, W: Write + Seek, E: Endian>(
-    mut src: R,
-    w: &mut binbin::Writer<'_, W, E>,
-) -> Result<u64> {
-    let len = std::io::copy(&mut src, w)?;
-    Ok(len)
-}
-
-fn write_metadata_sections_32<'a, W: Write + Seek, E: Endian>(
-    rodata_pos: u64,
-    sym_names: &[String],
-    syms: &[Symbol],
-    shstrtab: &[u8],
-    w: &mut binbin::Writer<'a, W, E>,
-) -> Result<TrailerMap> {
-    // At the point we're called, our position is at the end of the
-    // .rodata section body and we've not produced any other sections
-    // yet. We'll first produce all of the other section bodies and
-    // then finally write out the section header containing offsets
-    // back to these body positions.
-    const ALIGN: usize = 4;
-
-    // .shstrtab is a hard-coded string table of the four section names
-    // we always generate. This must be the first entry in the section
-    // header table below, because our ELF header points to it there.
-    w.align(ALIGN)?;
-    let shstrtab_start = w.position()?;
-    w.write(shstrtab)?;
-    let shstrtab_len = w.position()? - shstrtab_start;
+/// Decides where everything in an ELF file will go, and produces all of the
+/// bytes other than the contents of the data sections.
+///
+/// The header includes some values we can't know until we've laid out the
+/// rest of the file, so we write placeholders for those and then fill them
+/// in afterwards.
+fn layout_file(
+    hdr: &Header,
+    sections: &[SectionData],
+    syms: &[SymbolEntry],
+    relocs: &[Relocation],
+) -> FileLayout {
+    let class = hdr.class;
+    let mut w = Writer::new(hdr.encoding);
 
-    // .strtab is the table of our symbol names.
-    w.align(ALIGN)?;
-    let strtab_start = w.position()?;
-    w.write(0_u8)?; // string tables always start with a null
-    let mut symbol_name_idx: Vec<u32> = Vec::with_capacity(syms.len());
-    {
-        let mut idx: usize = 1;
+    let headmap = match class {
+        Class::ELF32 => write_hdr_32(hdr, &mut w),
+        Class::ELF64 => write_hdr_64(hdr, &mut w),
+    };
 
-        for name in sym_names.iter() {
-            symbol_name_idx.push(idx as u32);
-            w.write(name.as_bytes())?;
-            w.write(0_u8)?; // null terminator
-            idx += name.len() + 1;
+    // The data sections come first, in the order they were declared.
+    let mut pos = w.position();
+    let mut section_pos: Vec<u64> = Vec::with_capacity(sections.len());
+    for sect in sections.iter() {
+        let alignment = sect.alignment as u64;
+        pos = pos.next_multiple_of(alignment);
+        section_pos.push(pos);
+        if let SectionContents::Data(data) | SectionContents::Strings(data) = &sect.contents {
+            pos += data.len() as u64;
         }
     }
-    let strtab_len = w.position()? - strtab_start;
 
-    // .symtab is the table of the symbols themselves
-    w.align(ALIGN)?;
-    let symtab_start = w.position()?;
-    let mut rodata_size: u64 = 0;
-    let mut rodata_align: usize = 1;
-    if !syms.is_empty() {
-        // Symbol zero is a null symbol required by the ELF format
-        write_symbol_32(
-            w,
-            Symbol32 {
-                name_idx: 0,
-                value: 0,
-                size: 0,
-                info: 0,
-                other: 0,
-                section_idx: 0,
-            },
-        )?;
-        for (i, sym) in syms.iter().enumerate() {
-            write_symbol_32(
-                w,
-                Symbol32 {
-                    name_idx: symbol_name_idx[i],
-                    value: sym.rodata_offset as u32,
-                    size: sym.size as u32,
-                    info: (1 << 4) | 1_u8, // (STB_GLOBAL, STT_OBJECT)
-                    other: 0,
-                    section_idx: 2, // .rodata
-                },
-            )?;
-            rodata_size += sym.padded_size;
-            if sym.alignment > rodata_align {
-                rodata_align = sym.alignment;
-            }
-        }
+    let mut trailer = Writer::at(hdr.encoding, pos);
+    let map = write_metadata_sections(class, sections, &section_pos, syms, relocs, &mut trailer);
+
+    let section_header_pos = map.section_header_offset;
+    match class {
+        Class::ELF32 => w.patch(
+            headmap.section_header_offset_field,
+            section_header_pos as u32,
+        ),
+        Class::ELF64 => w.patch(headmap.section_header_offset_field, section_header_pos),
     }
-    let symtab_len = w.position()? - symtab_start;
+    w.patch(headmap.section_header_count_field, map.section_header_count);
 
-    // Now we'll write out the section headers. .shstrtab must be index 1
-    // and .rodata must be index 2 due to references we've left elsewhere
-    // in the file to those indices.
-    w.align(ALIGN)?;
-    let section_header_pos = w.position()?;
-    {
-        // Unused header index zero, as required by the ELF standard
-        write_section_header_32(
-            w,
-            SectionHeader32 {
-                name_idx: 0,
-                typ: SHT_NULL,
-                flags: 0,
-                addr: 0,
-                offset: 0,
-                size: 0,
-                link: 0,
-                info: 0,
-                addralign: 0,
-                entsize: 0,
-            },
-        )?;
+    FileLayout {
+        header: w.into_inner(),
+        section_pos,
+        trailer_pos: pos,
+        trailer: trailer.into_inner(),
     }
-    {
-        // .shstrtab (section header names table)
-        write_section_header_32(
-            w,
-            SectionHeader32 {
-                name_idx: SHSTRTAB_SHSTRTAB,
-                typ: SHT_STRTAB,
-                flags: SHF_STRINGS,
-                addr: 0,
-                offset: shstrtab_start as u32,
-                size: shstrtab_len as u32,
-                link: 0,
-                info: 0,
-                addralign: 0,
-                entsize: 1, // one byte per character
-            },
-        )?;
+}
+
+/// Writes a complete ELF file with the given layout.
+fn write_file<O: Write>(layout: &FileLayout, sections: &[SectionData], out: &mut O) -> Result<()> {
+    for piece in file_pieces(layout, sections) {
+        out.write_all(piece)?;
     }
-    {
-        // .rodata (the actual symbol contents)
-        write_section_header_32(
-            w,
-            SectionHeader32 {
-                name_idx: SHSTRTAB_RODATA,
-                typ: SHT_PROGBITS,
-                flags: SHF_ALLOC,
-                addr: 0, // linker will decide final addr
-                offset: rodata_pos as u32,
-                size: rodata_size as u32,
-                link: 0,
-                info: 0,
-                addralign: rodata_align as u32,
-                entsize: 0,
-            },
-        )?;
+    Ok(())
+}
+
+/// Returns the bytes of a complete ELF file with the given layout as a
+/// sequence of slices to be written in order, with zero padding between the
+/// data sections.
+fn file_pieces<'a>(layout: &'a FileLayout, sections: &'a [SectionData]) -> Vec<&'a [u8]> {
+    static ZEROS: [u8; 4096] = [0; 4096];
+    fn pad(pieces: &mut Vec<&[u8]>, mut n: u64) {
+        while n > 0 {
+            let chunk = n.min(ZEROS.len() as u64) as usize;
+            pieces.push(&ZEROS[..chunk]);
+            n -= chunk as u64;
+        }
     }
-    {
-        // .strtab (the symbol names table)
-        write_section_header_32(
-            w,
-            SectionHeader32 {
-                name_idx: SHSTRTAB_STRTAB,
-                typ: SHT_STRTAB,
-                flags: SHF_STRINGS,
-                addr: 0,
-                offset: strtab_start as u32,
-                size: strtab_len as u32,
-                link: 0,
-                info: 0,
-                addralign: 0,
-                entsize: 1, // one byte per character
-            },
-        )?;
+
+    let mut pieces: Vec<&[u8]> = vec![&layout.header[..]];
+    let mut pos = layout.header.len() as u64;
+    for (sect, &start) in sections.iter().zip(layout.section_pos.iter()) {
+        pad(&mut pieces, start - pos);
+        pos = start;
+        if let SectionContents::Data(data) | SectionContents::Strings(data) = &sect.contents {
+            pieces.push(&data[..]);
+            pos += data.len() as u64;
+        }
+    }
+    pad(&mut pieces, layout.trailer_pos - pos);
+    pieces.push(&layout.trailer[..]);
+    pieces
+}
+
+fn write_hdr_32(hdr: &Header, w: &mut Writer) -> HeaderMap {
+    write_ident(hdr, w);
+    w.write(ET_REL);
+    w.write(hdr.machine);
+    w.write(1_u32); // header version
+    w.write(0_u32); // entry point (none)
+    w.write(0_u32); // no program headers
+    let shoff_pos = w.position();
+    w.write(0_u32); // placeholder for section header offset
+    w.write(hdr.flags);
+    let header_size_pos = w.position();
+    w.write(0_u16); // placeholder for header size
+    w.write(0_u16); // no program header entries
+    w.write(0_u16); // no program header entries
+    w.write(40_u16); // section header entry size
+    let shnum_pos = w.position();
+    w.write(0_u16); // placeholder for section header entry count
+    w.write(1_u16); // section names are in section 1
+
+    let pos = w.position() as u16;
+    w.patch(header_size_pos, pos);
+
+    w.align(4);
+
+    HeaderMap {
+        section_header_offset_field: shoff_pos,
+        section_header_count_field: shnum_pos,
+    }
+}
+
+fn write_hdr_64(hdr: &Header, w: &mut Writer) -> HeaderMap {
+    write_ident(hdr, w);
+    w.write(ET_REL);
+    w.write(hdr.machine);
+    w.write(1_u32); // header version
+    w.write(0_u64); // entry point (none)
+    w.write(0_u64); // no program headers
+    let shoff_pos = w.position();
+    w.write(0_u64); // placeholder for section header offset
+    w.write(hdr.flags);
+    let header_size_pos = w.position();
+    w.write(0_u16); // placeholder for header size
+    w.write(0_u16); // no program header entries
+    w.write(0_u16); // no program header entries
+    w.write(64_u16); // section header entry size
+    let shnum_pos = w.position();
+    w.write(0_u16); // placeholder for section header entry count
+    w.write(1_u16); // section names are in section 1
+
+    let pos = w.position() as u16;
+    w.patch(header_size_pos, pos);
+
+    w.align(8);
+
+    HeaderMap {
+        section_header_offset_field: shoff_pos,
+        section_header_count_field: shnum_pos,
+    }
+}
+
+fn write_metadata_sections(
+    class: Class,
+    sections: &[SectionData],
+    section_pos: &[u64],
+    syms: &[SymbolEntry],
+    relocs: &[Relocation],
+    w: &mut Writer,
+) -> TrailerMap {
+    // At the point we're called, our position is just after the data
+    // sections, which are at the positions in section_pos. We'll first
+    // produce the bodies of the metadata sections and then finally write out
+    // the section header containing offsets back to all of the body
+    // positions.
+    let align: usize = match class {
+        Class::ELF32 => 4,
+        Class::ELF64 => 8,
+    };
+    let symtab_entsize: u64 = match class {
+        Class::ELF32 => 16,
+        Class::ELF64 => 24,
+    };
+
+    // Each data section with relocations gets a relocation section, named
+    // after the data section with either a .rel or .rela prefix depending
+    // on the relocation style for the target machine.
+    let mut reloc_sections: Vec<(Section, Vec<&Relocation>)> = Vec::new();
+    for (i, _) in sections.iter().enumerate() {
+        let section_relocs: Vec<&Relocation> = relocs
+            .iter()
+            .filter(|reloc| reloc.section == Section(i))
+            .collect();
+        if !section_relocs.is_empty() {
+            reloc_sections.push((Section(i), section_relocs));
+        }
     }
+
+    // .shstrtab starts with the names of the metadata sections we always
+    // generate, followed by the names of each of the data sections and
+    // then the relocation sections. This must be the first entry in the
+    // section header table below, because our ELF header points to it there.
+    w.align(align);
+    let shstrtab_start = w.position();
+    w.write(SHSTRTAB);
+    let mut section_name_idx: Vec<u32> = Vec::with_capacity(sections.len());
+    let mut reloc_name_idx: Vec<u32> = Vec::with_capacity(reloc_sections.len());
     {
-        // .symtab (the symbol table itself)
-        write_section_header_32(
-            w,
-            SectionHeader32 {
-                name_idx: SHSTRTAB_SYMTAB,
-                typ: SHT_SYMTAB,
-                flags: 0,
-                addr: 0,
-                offset: symtab_start as u32,
-                size: symtab_len as u32,
-                link: 3,      // symbol names are in section 3 (.strtab)
-                info: 1,      // symbol 1 is the first global symbol
-                addralign: 0, // no alignment requirements
-                entsize: 16,
-            },
-        )?;
+        let mut idx: usize = SHSTRTAB.len();
+
+        for sect in sections.iter() {
+            section_name_idx.push(idx as u32);
+            let name = sect.name();
+            w.write(name.as_bytes());
+            w.write(0_u8); // null terminator
+            idx += name.len() + 1;
+        }
+        for (section, section_relocs) in reloc_sections.iter() {
+            reloc_name_idx.push(idx as u32);
+            let prefix = section_relocs[0].kind.section_prefix();
+            let name = sections[section.0].name();
+            w.write(prefix.as_bytes());
+            w.write(name.as_bytes());
+            w.write(0_u8); // null terminator
+            idx += prefix.len() + name.len() + 1;
+        }
     }
+    let shstrtab_len = w.position() - shstrtab_start;
 
-    Ok(TrailerMap {
-        section_header_offset: section_header_pos,
-    })
-}
+    // Any relocation targets that aren't defined in this file must be
+    // declared as undefined symbols, to be resolved by the linker.
+    let mut undefined: Vec<SymbolEntry> = Vec::new();
+    for reloc in relocs.iter() {
+        let defined = syms
+            .iter()
+            .chain(undefined.iter())
+            .any(|sym| sym.name == reloc.target);
+        if !defined {
+            undefined.push(SymbolEntry {
+                name: reloc.target.clone(),
+                value: 0,
+                size: 0,
+                section_idx: SHN_UNDEF,
+                alignment: 1,
+                typ: STT_NOTYPE,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            });
+        }
+    }
 
-fn write_metadata_sections_64<'a, W: Write + Seek, E: Endian>(
-    rodata_pos: u64,
-    sym_names: &[String],
-    syms: &[Symbol],
-    shstrtab: &[u8],
-    w: &mut binbin::Writer<'a, W, E>,
-) -> Result<TrailerMap> {
-    // At the point we're called, our position is at the end of the
-    // .rodata section body and we've not produced any other sections
-    // yet. We'll first produce all of the other section bodies and
-    // then finally write out the section header containing offsets
-    // back to these body positions.
-    const ALIGN: usize = 8;
-
-    // .shstrtab is a hard-coded string table of the four section names
-    // we always generate. This must be the first entry in the section
-    // header table below, because our ELF header points to it there.
-    w.align(ALIGN)?;
-    let shstrtab_start = w.position()?;
-    w.write(shstrtab)?;
-    let shstrtab_len = w.position()? - shstrtab_start;
+    // The ELF format requires all of the local symbols to appear before
+    // any global or weak symbols, so we'll reorder them here while otherwise
+    // preserving the order they were defined in.
+    let syms: Vec<&SymbolEntry> = syms
+        .iter()
+        .filter(|sym| sym.binding == Binding::Local)
+        .chain(syms.iter().filter(|sym| sym.binding != Binding::Local))
+        .chain(undefined.iter())
+        .collect();
+    let symbol_idx: BTreeMap<&str, u32> = syms
+        .iter()
+        .enumerate()
+        .map(|(i, sym)| (&sym.name[..], i as u32 + 1)) // index zero is the null symbol
+        .collect();
+    let local_count = syms
+        .iter()
+        .take_while(|sym| sym.binding == Binding::Local)
+        .count();
 
     // .strtab is the table of our symbol names.
-    w.align(ALIGN)?;
-    let strtab_start = w.position()?;
-    w.write(0_u8)?; // string tables always start with a null
+    w.align(align);
+    let strtab_start = w.position();
+    w.write(0_u8); // string tables always start with a null
     let mut symbol_name_idx: Vec<u32> = Vec::with_capacity(syms.len());
     {
         let mut idx: usize = 1;
 
-        for name in sym_names.iter() {
+        for sym in syms.iter() {
+            let name = &sym.name;
             symbol_name_idx.push(idx as u32);
-            w.write(name.as_bytes())?;
-            w.write(0_u8)?; // null terminator
+            w.write(name.as_bytes());
+            w.write(0_u8); // null terminator
             idx += name.len() + 1;
         }
     }
-    let strtab_len = w.position()? - strtab_start;
+    let strtab_len = w.position() - strtab_start;
 
     // .symtab is the table of the symbols themselves
-    w.align(ALIGN)?;
-    let symtab_start = w.position()?;
-    let mut rodata_size: u64 = 0;
-    let mut rodata_align: usize = 1;
+    w.align(align);
+    let symtab_start = w.position();
     if !syms.is_empty() {
         // Symbol zero is a null symbol required by the ELF format
-        write_symbol_64(
+        write_symbol(
             w,
+            class,
             Symbol64 {
                 name_idx: 0,
                 value: 0,
@@ -578,36 +1569,64 @@ fn write_metadata_sections_64<'a, W: Write + Seek, E: Endian>(
                 other: 0,
                 section_idx: 0,
             },
-        )?;
-        for (i, v) in syms.iter().enumerate() {
-            write_symbol_64(
+        );
+        for (i, sym) in syms.iter().enumerate() {
+            write_symbol(
                 w,
+                class,
                 Symbol64 {
                     name_idx: symbol_name_idx[i],
-                    value: v.rodata_offset,
-                    size: v.size,
-                    info: (1 << 4) | 1_u8, // (STB_GLOBAL, STT_OBJECT)
-                    other: 0,
-                    section_idx: 2, // .rodata
+                    value: sym.value,
+                    size: sym.size,
+                    info: (sym.binding.value() << 4) | sym.typ,
+                    other: sym.visibility as u8,
+                    section_idx: sym.section_idx,
                 },
-            )?;
-            rodata_size += v.padded_size;
-            if v.alignment > rodata_align {
-                rodata_align = v.alignment;
+            );
+        }
+    }
+    let symtab_len = w.position() - symtab_start;
+
+    // The relocation sections, if any, are tables of places in the data
+    // sections that the linker must fill in with the address of a symbol.
+    let mut reloc_pos: Vec<(u64, u64)> = Vec::with_capacity(reloc_sections.len());
+    for (_, section_relocs) in reloc_sections.iter() {
+        w.align(align);
+        let start = w.position();
+        for reloc in section_relocs.iter() {
+            let sym_idx = symbol_idx[&reloc.target[..]];
+            match class {
+                Class::ELF32 => {
+                    w.write(reloc.offset as u32);
+                    w.write((sym_idx << 8) | (reloc.kind.typ & 0xff));
+                    if reloc.kind.rela {
+                        w.write(reloc.addend as i32 as u32);
+                    }
+                }
+                Class::ELF64 => {
+                    w.write(reloc.offset);
+                    w.write(((sym_idx as u64) << 32) | reloc.kind.typ as u64);
+                    if reloc.kind.rela {
+                        w.write(reloc.addend as u64);
+                    }
+                }
             }
         }
+        reloc_pos.push((start, w.position() - start));
     }
-    let symtab_len = w.position()? - symtab_start;
 
     // Now we'll write out the section headers. .shstrtab must be index 1
-    // and .rodata must be index 2 due to references we've left elsewhere
-    // in the file to those indices.
-    w.align(ALIGN)?;
-    let section_header_pos = w.position()?;
+    // due to the reference to it in the ELF header, and the data sections
+    // follow from index 2 so that the symbol table can refer to them. The
+    // relocation sections, if any, come last.
+    let strtab_idx = FIRST_DATA_SECTION_IDX as u32 + sections.len() as u32;
+    w.align(align);
+    let section_header_pos = w.position();
     {
         // Unused header index zero, as required by the ELF standard
-        write_section_header_64(
+        write_section_header(
             w,
+            class,
             SectionHeader64 {
                 name_idx: 0,
                 typ: SHT_NULL,
@@ -620,12 +1639,13 @@ fn write_metadata_sections_64<'a, W: Write + Seek, E: Endian>(
                 addralign: 0,
                 entsize: 0,
             },
-        )?;
+        );
     }
     {
         // .shstrtab (section header names table)
-        write_section_header_64(
+        write_section_header(
             w,
+            class,
             SectionHeader64 {
                 name_idx: SHSTRTAB_SHSTRTAB,
                 typ: SHT_STRTAB,
@@ -638,30 +1658,32 @@ fn write_metadata_sections_64<'a, W: Write + Seek, E: Endian>(
                 addralign: 0,
                 entsize: 1, // one byte per character
             },
-        )?;
+        );
     }
-    {
-        // .rodata (the actual symbol contents)
-        write_section_header_64(
+    for (i, sect) in sections.iter().enumerate() {
+        // The data sections (the actual symbol contents)
+        write_section_header(
             w,
+            class,
             SectionHeader64 {
-                name_idx: SHSTRTAB_RODATA,
-                typ: SHT_PROGBITS,
-                flags: SHF_ALLOC as u64,
+                name_idx: section_name_idx[i],
+                typ: sect.typ(),
+                flags: sect.flags() as u64,
                 addr: 0, // linker will decide final addr
-                offset: rodata_pos,
-                size: rodata_size,
+                offset: section_pos[i],
+                size: sect.contents.len(),
                 link: 0,
                 info: 0,
-                addralign: rodata_align as u64,
-                entsize: 0,
+                addralign: sect.alignment as u64,
+                entsize: sect.entsize(),
             },
-        )?;
+        );
     }
     {
         // .strtab (the symbol names table)
-        write_section_header_64(
+        write_section_header(
             w,
+            class,
             SectionHeader64 {
                 name_idx: SHSTRTAB_STRTAB,
                 typ: SHT_STRTAB,
@@ -674,12 +1696,13 @@ fn write_metadata_sections_64<'a, W: Write + Seek, E: Endian>(
                 addralign: 0,
                 entsize: 1, // one byte per character
             },
-        )?;
+        );
     }
     {
         // .symtab (the symbol table itself)
-        write_section_header_64(
+        write_section_header(
             w,
+            class,
             SectionHeader64 {
                 name_idx: SHSTRTAB_SYMTAB,
                 typ: SHT_SYMTAB,
@@ -687,104 +1710,776 @@ fn write_metadata_sections_64<'a, W: Write + Seek, E: Endian>(
                 addr: 0,
                 offset: symtab_start,
                 size: symtab_len,
-                link: 3,      // symbol names are in section 3 (.strtab)
-                info: 1,      // symbol 1 is the first global symbol
-                addralign: 0, // no alignment requirements
-                entsize: 24,
+                link: strtab_idx,             // symbol names are in .strtab
+                info: 1 + local_count as u32, // index of the first global symbol
+                addralign: 0,                 // no alignment requirements
+                entsize: symtab_entsize,
             },
-        )?;
+        );
+    }
+    for (i, (section, section_relocs)) in reloc_sections.iter().enumerate() {
+        // .rel or .rela for a data section
+        let kind = section_relocs[0].kind;
+        let (typ, entsize) = match (kind.rela, class) {
+            (false, Class::ELF32) => (SHT_REL, 8),
+            (false, Class::ELF64) => (SHT_REL, 16),
+            (true, Class::ELF32) => (SHT_RELA, 12),
+            (true, Class::ELF64) => (SHT_RELA, 24),
+        };
+        write_section_header(
+            w,
+            class,
+            SectionHeader64 {
+                name_idx: reloc_name_idx[i],
+                typ,
+                flags: SHF_INFO_LINK as u64,
+                addr: 0,
+                offset: reloc_pos[i].0,
+                size: reloc_pos[i].1,
+                link: strtab_idx + 1,              // symbols are in .symtab
+                info: section.header_idx() as u32, // the section to relocate
+                addralign: align as u64,
+                entsize,
+            },
+        );
     }
 
-    Ok(TrailerMap {
+    TrailerMap {
         section_header_offset: section_header_pos,
-    })
+        section_header_count: strtab_idx as u16 + 2 + reloc_sections.len() as u16,
+    }
 }
 
-fn write_section_header_32<W: Write + Seek, E: Endian>(
-    w: &mut binbin::Writer<'_, W, E>,
-    hdr: SectionHeader32,
-) -> Result<()> {
-    w.write(hdr.name_idx)?; // index into .shstrtab
-    w.write(hdr.typ)?; // type
-    w.write(hdr.flags)?; // no flags
-    w.write(hdr.addr)?; // no addr
-    w.write(hdr.offset)?; // offset
-    w.write(hdr.size)?; // size
-    w.write(hdr.link)?; // symbol names are in section 3 (.strtab)
-    w.write(hdr.info)?; // symbol 1 is the first global symbol
-    w.write(hdr.addralign)?; // no alignment
-    w.write(hdr.entsize)?; // no alignment
-    Ok(())
+/// Writes a section header in the format for the given class. The 32-bit
+/// format just truncates the wider fields of [`SectionHeader64`].
+fn write_section_header(w: &mut Writer, class: Class, hdr: SectionHeader64) {
+    match class {
+        Class::ELF32 => write_section_header_32(
+            w,
+            SectionHeader32 {
+                name_idx: hdr.name_idx,
+                typ: hdr.typ,
+                flags: hdr.flags as u32,
+                addr: hdr.addr as u32,
+                offset: hdr.offset as u32,
+                size: hdr.size as u32,
+                link: hdr.link,
+                info: hdr.info,
+                addralign: hdr.addralign as u32,
+                entsize: hdr.entsize as u32,
+            },
+        ),
+        Class::ELF64 => write_section_header_64(w, hdr),
+    }
 }
 
-fn write_section_header_64<W: Write + Seek, E: Endian>(
-    w: &mut binbin::Writer<'_, W, E>,
-    hdr: SectionHeader64,
-) -> Result<()> {
-    w.write(hdr.name_idx)?; // index into .shstrtab
-    w.write(hdr.typ)?; // type
-    w.write(hdr.flags)?; // no flags
-    w.write(hdr.addr)?; // no addr
-    w.write(hdr.offset)?; // offset
-    w.write(hdr.size)?; // size
-    w.write(hdr.link)?; // symbol names are in section 3 (.strtab)
-    w.write(hdr.info)?; // symbol 1 is the first global symbol
-    w.write(hdr.addralign)?; // no alignment
-    w.write(hdr.entsize)?; // no alignment
-    Ok(())
+/// Writes a symbol table entry in the format for the given class. The 32-bit
+/// format just truncates the wider fields of [`Symbol64`].
+fn write_symbol(w: &mut Writer, class: Class, sym: Symbol64) {
+    match class {
+        Class::ELF32 => write_symbol_32(
+            w,
+            Symbol32 {
+                name_idx: sym.name_idx,
+                value: sym.value as u32,
+                size: sym.size as u32,
+                info: sym.info,
+                other: sym.other,
+                section_idx: sym.section_idx,
+            },
+        ),
+        Class::ELF64 => write_symbol_64(w, sym),
+    }
 }
 
-fn write_symbol_32<W: Write + Seek, E: Endian>(
-    w: &mut binbin::Writer<'_, W, E>,
-    sym: Symbol32,
-) -> Result<()> {
-    w.write(sym.name_idx)?; // index into .strtab
-    w.write(sym.value)?;
-    w.write(sym.size)?;
-    w.write(sym.info)?;
-    w.write(sym.other)?;
-    w.write(sym.section_idx)?;
-    Ok(())
+fn write_section_header_32(w: &mut Writer, hdr: SectionHeader32) {
+    w.write(hdr.name_idx); // index into .shstrtab
+    w.write(hdr.typ); // type
+    w.write(hdr.flags); // no flags
+    w.write(hdr.addr); // no addr
+    w.write(hdr.offset); // offset
+    w.write(hdr.size); // size
+    w.write(hdr.link); // symbol names are in section 3 (.strtab)
+    w.write(hdr.info); // symbol 1 is the first global symbol
+    w.write(hdr.addralign); // no alignment
+    w.write(hdr.entsize); // no alignment
 }
 
-fn write_symbol_64<W: Write + Seek, E: Endian>(
-    w: &mut binbin::Writer<'_, W, E>,
-    sym: Symbol64,
-) -> Result<()> {
-    w.write(sym.name_idx)?; // index into .strtab
-    w.write(sym.info)?;
-    w.write(sym.other)?;
-    w.write(sym.section_idx)?;
-    w.write(sym.value)?;
-    w.write(sym.size)?;
-    Ok(())
+fn write_section_header_64(w: &mut Writer, hdr: SectionHeader64) {
+    w.write(hdr.name_idx); // index into .shstrtab
+    w.write(hdr.typ); // type
+    w.write(hdr.flags); // no flags
+    w.write(hdr.addr); // no addr
+    w.write(hdr.offset); // offset
+    w.write(hdr.size); // size
+    w.write(hdr.link); // symbol names are in section 3 (.strtab)
+    w.write(hdr.info); // symbol 1 is the first global symbol
+    w.write(hdr.addralign); // no alignment
+    w.write(hdr.entsize); // no alignment
 }
 
-fn write_ident<'a, W: Write + Seek, E: Endian>(
-    hdr: &Header,
-    w: &mut binbin::Writer<'a, W, E>,
-) -> Result<()> {
+fn write_symbol_32(w: &mut Writer, sym: Symbol32) {
+    w.write(sym.name_idx); // index into .strtab
+    w.write(sym.value);
+    w.write(sym.size);
+    w.write(sym.info);
+    w.write(sym.other);
+    w.write(sym.section_idx);
+}
+
+fn write_symbol_64(w: &mut Writer, sym: Symbol64) {
+    w.write(sym.name_idx); // index into .strtab
+    w.write(sym.info);
+    w.write(sym.other);
+    w.write(sym.section_idx);
+    w.write(sym.value);
+    w.write(sym.size);
+}
+
+fn write_ident(hdr: &Header, w: &mut Writer) {
     // e_ident bytes
-    w.write(&b"\x7fELF"[..])?;
-    w.write(hdr.class as u8)?;
-    w.write(hdr.encoding as u8)?;
-    w.write(1_u8)?; // file version 1
-    w.write(0_u8)?; // no particular ABI
-    w.skip(8)?; // unused ident bytes
-    Ok(())
+    w.write(&b"\x7fELF"[..]);
+    w.write(hdr.class as u8);
+    w.write(hdr.encoding as u8);
+    w.write(1_u8); // file version 1
+    w.write(0_u8); // no particular ABI
+    w.skip(8); // unused ident bytes
 }
 
 struct HeaderMap {
     section_header_offset_field: u64,
+    section_header_count_field: u64,
 }
 
 struct TrailerMap {
     section_header_offset: u64,
+    section_header_count: u16,
+}
+
+/// Describes why a symbol name was rejected by a [`Builder`].
+///
+/// The `add_symbol` family of functions return [`std::io::Error`] for
+/// consistency with the rest of the API, so this is returned as the inner
+/// error of an error of kind [`ErrorKind::InvalidInput`]. Use
+/// [`Error::get_ref`] and then `downcast_ref` to recover it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SymbolNameError {
+    /// The symbol name is empty.
+    Empty,
+
+    /// The symbol name contains a null byte, which would terminate it early
+    /// in the string table.
+    ContainsNul(String),
+
+    /// A symbol of the same name was already defined.
+    Duplicate(String),
+
+    /// The symbol name isn't a valid C identifier, and the builder was
+    /// configured with [`Builder::set_require_c_identifiers`].
+    NotCIdentifier(String),
+
+    /// The symbol name isn't a C++ variable name qualified with namespaces,
+    /// and the builder was configured with [`Builder::set_cpp_names`].
+    NotCppName(String),
+}
+
+impl core::fmt::Display for SymbolNameError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SymbolNameError::Empty => write!(f, "symbol name must not be empty"),
+            SymbolNameError::ContainsNul(name) => {
+                write!(f, "symbol name {:?} contains a null byte", name)
+            }
+            SymbolNameError::Duplicate(name) => {
+                write!(f, "symbol {:?} is defined more than once", name)
+            }
+            SymbolNameError::NotCIdentifier(name) => {
+                write!(f, "symbol name {:?} is not a valid C identifier", name)
+            }
+            SymbolNameError::NotCppName(name) => {
+                write!(
+                    f,
+                    "symbol name {:?} is not a valid qualified C++ name",
+                    name
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SymbolNameError {}
+
+impl From<SymbolNameError> for Error {
+    fn from(err: SymbolNameError) -> Self {
+        Error::new(ErrorKind::InvalidInput, err)
+    }
+}
+
+/// The error for a file with more sections than the section header table
+/// can index without extended section numbering.
+fn too_many_sections() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "too many sections for the ELF section header table",
+    )
+}
+
+/// The error for a zeroed symbol that would make its section larger than
+/// the destination ELF file can describe.
+fn zeroed_section_too_large(class: Class) -> Error {
+    let msg = match class {
+        Class::ELF32 => "zeroed section size must fit in 32 bits for ELF32",
+        Class::ELF64 => "zeroed section size must fit in 64 bits",
+    };
+    Error::new(ErrorKind::InvalidInput, msg)
+}
+
+/// Checks that the given options can be written to the symbol table.
+fn check_options(opts: &SymbolOptions) -> Result<()> {
+    if let Binding::Other(value) = opts.binding {
+        if value <= 2 {
+            // These would be written as local, global or weak symbols but
+            // not ordered as such in the symbol table.
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "symbol bindings 0 to 2 must be given as Local, Global or Weak",
+            ));
+        }
+    }
+    if opts.binding.value() > 0xf {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "symbol binding must be less than 16",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the given symbol alignment is a power of two, which also
+/// rules out zero.
+fn check_alignment(alignment: usize) -> Result<()> {
+    if !alignment.is_power_of_two() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "symbol alignment must be a power of two",
+        ));
+    }
+    Ok(())
+}
+
+fn is_c_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Options for how a symbol is placed and exported, for use with
+/// [`Builder::add_symbol_with`] and [`Builder::add_zeroed_symbol_with`].
+///
+/// The default options produce a global symbol with default visibility,
+/// placed in the builder's default section and aligned to the word size
+/// of the destination ELF file.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct SymbolOptions {
+    /// The section to place the symbol in, or `None` to use the default.
+    pub section: Option<Section>,
+
+    /// The alignment of the symbol data in bytes, or `None` to use the word
+    /// size of the destination ELF file. The alignment must be a power of
+    /// two.
+    pub alignment: Option<usize>,
+
+    /// How the symbol is visible to the linker.
+    pub binding: Binding,
+
+    /// How the symbol is visible to other components once linked.
+    pub visibility: Visibility,
+}
+
+/// ELF symbol binding, which determines how the linker resolves references
+/// to a symbol.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum Binding {
+    /// The symbol is visible only within the object file that defines it.
+    Local = 0,
+
+    /// The symbol is visible to all object files being linked together.
+    #[default]
+    Global = 1,
+
+    /// Like `Global`, but a global definition of the same name elsewhere
+    /// takes precedence over this one.
+    Weak = 2,
+
+    /// Some other binding, such as `STB_GNU_UNIQUE` (10), which appears in
+    /// linked C++ programs and is reported by [`Reader`]. The value must be
+    /// less than 16 to fit in a symbol table entry, and mustn't be the value
+    /// of one of the other variants, which the builder rejects.
+    Other(u8),
+}
+
+impl Binding {
+    /// Returns the binding's value in a symbol table entry.
+    fn value(self) -> u8 {
+        match self {
+            Binding::Local => 0,
+            Binding::Global => 1,
+            Binding::Weak => 2,
+            Binding::Other(value) => value,
+        }
+    }
+}
+
+/// ELF symbol visibility, which determines whether a symbol is exported
+/// from the shared library or executable it's linked into.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(u8)]
+pub enum Visibility {
+    /// Visibility is determined by the symbol's binding.
+    #[default]
+    Default = 0,
+
+    /// Like `Hidden`, with additional processor-specific semantics.
+    Internal = 1,
+
+    /// The symbol is not visible outside of the linked component.
+    Hidden = 2,
+
+    /// The symbol is visible outside of the linked component, but references
+    /// from within the component always resolve to this definition.
+    Protected = 3,
+}
+
+/// Templates for the names of the companion symbols enabled by
+/// [`Builder::set_companion_symbols`].
+///
+/// Each template is a symbol name in which the first `{}` is replaced with
+/// the name given when adding the symbol.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CompanionNaming {
+    /// The name of the symbol referring to the start of the data, which is
+    /// the symbol that would be defined if companions were disabled.
+    pub start: String,
+
+    /// The name of the symbol referring to the address just after the end
+    /// of the data.
+    pub end: String,
+
+    /// The name of the absolute symbol whose value is the size of the data.
+    pub size: String,
+}
+
+impl CompanionNaming {
+    /// Returns the naming scheme used by `objcopy -I binary` and
+    /// `ld -b binary`: `_binary_NAME_start`, `_binary_NAME_end` and
+    /// `_binary_NAME_size`.
+    pub fn objcopy() -> Self {
+        Self {
+            start: String::from("_binary_{}_start"),
+            end: String::from("_binary_{}_end"),
+            size: String::from("_binary_{}_size"),
+        }
+    }
+
+    /// Returns true if every template contains `{}`.
+    fn is_valid(&self) -> bool {
+        [&self.start, &self.end, &self.size]
+            .iter()
+            .all(|template| template.contains("{}"))
+    }
+
+    fn start_name(&self, name: &str) -> String {
+        self.start.replacen("{}", name, 1)
+    }
+
+    fn end_name(&self, name: &str) -> String {
+        self.end.replacen("{}", name, 1)
+    }
+
+    fn size_name(&self, name: &str) -> String {
+        self.size.replacen("{}", name, 1)
+    }
+}
+
+impl Default for CompanionNaming {
+    fn default() -> Self {
+        Self::objcopy()
+    }
+}
+
+/// The contents of a symbol that mixes literal data with pointers to other
+/// symbols, for use with [`Builder::add_relocatable_symbol`].
+///
+/// This can describe arrays of structures such as
+/// `struct { const uint8_t *data; size_t len; }`, where the pointer fields
+/// are filled in by the linker. Words and pointers are encoded using the
+/// word size and encoding of the destination ELF file, and the caller is
+/// responsible for adding any padding bytes the structure layout requires.
+///
+/// ```
+/// let mut table = elfbin::RelocatableData::new();
+/// table.pointer("logo", 0).word(1024);
+/// table.pointer("font", 0).word(512);
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RelocatableData {
+    fields: Vec<Field>,
+}
+
+impl RelocatableData {
+    /// Returns an empty `RelocatableData`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the given bytes literally.
+    pub fn bytes(&mut self, v: &[u8]) -> &mut Self {
+        self.fields.push(Field::Bytes(v.to_vec()));
+        self
+    }
+
+    /// Appends an unsigned integer the size of a pointer, such as a C
+    /// `size_t` or `uintptr_t`.
+    pub fn word(&mut self, v: u64) -> &mut Self {
+        self.fields.push(Field::Word(v));
+        self
+    }
+
+    /// Appends a pointer to the symbol with the given name, offset by
+    /// `addend` bytes.
+    ///
+    /// The name must be acceptable as a symbol name in the builder the data
+    /// is added to, although it needn't be defined there. For ELF32, the
+    /// addend must fit in a signed 32-bit integer.
+    pub fn pointer(&mut self, target: impl Into<String>, addend: i64) -> &mut Self {
+        self.fields.push(Field::Pointer {
+            target: target.into(),
+            addend,
+        });
+        self
+    }
+
+    fn has_pointers(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|field| matches!(field, Field::Pointer { .. }))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Field {
+    Bytes(Vec<u8>),
+    Word(u64),
+    Pointer { target: String, addend: i64 },
+}
+
+/// A relocation waiting to be written by [`Builder::close`].
+struct Relocation {
+    section: Section,
+    offset: u64,
+    target: String,
+    kind: RelocationKind,
+    addend: i64,
+}
+
+/// The machine-specific details of a pointer-sized absolute relocation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct RelocationKind {
+    typ: u32,
+
+    /// True if the relocation entries include an explicit addend (RELA),
+    /// or false if the addend is stored at the relocated location (REL).
+    rela: bool,
+}
+
+impl RelocationKind {
+    fn section_prefix(self) -> &'static str {
+        if self.rela {
+            ".rela"
+        } else {
+            ".rel"
+        }
+    }
+}
+
+/// Returns the relocation to use for a pointer-sized absolute address on
+/// the given machine, or an error if we don't know about that machine.
+fn pointer_relocation(machine: u16, class: Class) -> Result<RelocationKind> {
+    let (typ, rela) = match (machine, class) {
+        (EM_386, Class::ELF32) => (R_386_32, false),
+        (EM_ARM, Class::ELF32) => (R_ARM_ABS32, false),
+        (EM_X86_64, Class::ELF32) => (R_X86_64_32, true),
+        (EM_X86_64, Class::ELF64) => (R_X86_64_64, true),
+        (EM_AARCH64, Class::ELF32) => (R_AARCH64_P32_ABS32, true),
+        (EM_AARCH64, Class::ELF64) => (R_AARCH64_ABS64, true),
+        (EM_RISCV, Class::ELF32) => (R_RISCV_32, true),
+        (EM_RISCV, Class::ELF64) => (R_RISCV_64, true),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "pointers are not supported for machine {:#x} with {:?}",
+                    machine, class
+                ),
+            ))
+        }
+    };
+    Ok(RelocationKind { typ, rela })
+}
+
+/// Returns the 64-bit FNV-1a hash of the given data, which we use to find
+/// candidates for deduplication.
+fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// A data symbol whose data is being appended to a section, returned by
+/// [`Builder::begin_data_symbol`] and [`Builder::begin_data_symbol_in`].
+///
+/// Dropping it without calling [`end`](Self::end), such as when an async
+/// read is cancelled, discards the data and the padding added for it, along
+/// with the section if it was created for the symbol.
+struct PendingData<'a, W: Write> {
+    builder: &'a mut Builder<W>,
+    section: Section,
+    alignment: usize,
+
+    /// The length of the section before the padding for the symbol.
+    offset: u64,
+
+    /// The number of sections before any section that was created for the
+    /// symbol.
+    section_count: usize,
+
+    /// Whether the symbol has been defined, so that its data must be kept.
+    defined: bool,
+}
+
+impl<W: Write> PendingData<'_, W> {
+    /// Returns the buffer to append the symbol's data to.
+    fn data(&mut self) -> &mut Vec<u8> {
+        match &mut self.builder.sections[self.section.0].contents {
+            SectionContents::Data(data) | SectionContents::Strings(data) => data,
+            SectionContents::Zeroed(_) => unreachable!("begin_data_symbol rejects zeroed sections"),
+        }
+    }
+
+    /// Defines a symbol referring to the appended data, or if `result` is an
+    /// error, discards the data.
+    fn end(
+        mut self,
+        name: String,
+        opts: &SymbolOptions,
+        result: Result<()>,
+        deduplicate: bool,
+    ) -> Result<Symbol> {
+        let (section, alignment, offset) = (self.section, self.alignment, self.offset);
+        let start = offset.next_multiple_of(alignment as u64);
+        let length = self.data().len() as u64 - start;
+        // If either of these fail then dropping self discards the data.
+        result?;
+        check_element_size(length, self.builder.element_size())?;
+        self.defined = true;
+
+        let builder = &mut *self.builder;
+        let skip = start - offset;
+        let padded_size = length + skip;
+        let sect = &mut builder.sections[section.0];
+        if alignment > sect.alignment {
+            sect.alignment = alignment;
+        }
+        let data = match &mut sect.contents {
+            SectionContents::Data(data) | SectionContents::Strings(data) => data,
+            SectionContents::Zeroed(_) => unreachable!("begin_data_symbol rejects zeroed sections"),
+        };
+
+        let index = match &mut builder.blob_index {
+            Some(index) if deduplicate && !sect.writable => Some(index),
+            _ => None,
+        };
+        if let Some(index) = index {
+            let start = start as usize;
+            let blob = &data[start..];
+            let candidates = index.entry((section, content_hash(blob))).or_default();
+            let existing = candidates
+                .iter()
+                .copied()
+                .find(|&(candidate, candidate_len)| {
+                    let candidate_end = candidate + candidate_len;
+                    candidate_len == length
+                        && candidate % alignment as u64 == 0
+                        && data[candidate as usize..candidate_end as usize] == *blob
+                })
+                .map(|(candidate, _)| candidate);
+            match existing {
+                Some(existing) => {
+                    // The new data is a copy of something we already wrote,
+                    // so we'll discard it and the padding we added for it.
+                    data.truncate(offset as usize);
+                    builder.sections[section.0].shared_data = true;
+                    builder.deduplicated_bytes += length;
+                    let sym = Symbol {
+                        section,
+                        rodata_offset: existing,
+                        size: length,
+                        padded_size: 0,
+                        alignment,
+                    };
+                    builder.push_symbol(name, sym, opts);
+                    return Ok(sym);
+                }
+                None => candidates.push((start as u64, length)),
+            }
+        }
+
+        let sym = Symbol {
+            section,
+            rodata_offset: start,
+            size: length,
+            padded_size,
+            alignment,
+        };
+        builder.push_symbol(name, sym, opts);
+        Ok(sym)
+    }
+}
+
+impl<W: Write> Drop for PendingData<'_, W> {
+    fn drop(&mut self) {
+        if !self.defined {
+            let offset = self.offset as usize;
+            self.data().truncate(offset);
+            self.builder.sections.truncate(self.section_count);
+        }
+    }
+}
+
+/// Previously-written blobs as (offset, length) pairs, keyed by the section
+/// they were written into and a hash of their contents.
+type BlobIndex = BTreeMap<(Section, u64), Vec<(u64, u64)>>;
+
+/// A symbol table entry waiting to be written by [`Builder::close`].
+struct SymbolEntry {
+    name: String,
+    value: u64,
+    size: u64,
+    section_idx: u16,
+
+    /// The alignment the symbol was placed with, which ELF doesn't record
+    /// but which [`Builder::close_verified`] checks.
+    alignment: u64,
+    typ: u8,
+    binding: Binding,
+    visibility: Visibility,
+}
+
+/// Identifies a data section in a [`Builder`], as returned by
+/// [`Builder::add_section`] or [`Builder::default_section`].
+///
+/// A section can only be used with the builder that returned it. Functions
+/// given a section that doesn't exist in their builder return an error of
+/// kind [`ErrorKind::InvalidInput`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Section(usize);
+
+impl Section {
+    /// Returns the index of this section's header in the section header
+    /// table. [`Builder::close`] checks that there are few enough sections
+    /// for this to fit.
+    fn header_idx(self) -> u16 {
+        FIRST_DATA_SECTION_IDX + self.0 as u16
+    }
+}
+
+/// The contents of a data section, buffered until [`Builder::close`].
+struct SectionData {
+    /// The section name, or `None` to use a default name chosen based on
+    /// the other section settings.
+    name: Option<String>,
+    contents: SectionContents,
+    alignment: usize,
+    writable: bool,
+
+    /// Whether deduplication has made some symbols in the section share
+    /// data, so that it mustn't become writable.
+    shared_data: bool,
+}
+
+enum SectionContents {
+    /// The section contains the given bytes.
+    Data(Vec<u8>),
+
+    /// The section contains the given number of zero bytes, which are not
+    /// included in the file.
+    Zeroed(u64),
+
+    /// The section contains the given NUL-terminated strings, which a linker
+    /// may merge with identical strings from other object files.
+    Strings(Vec<u8>),
+}
+
+impl SectionContents {
+    fn len(&self) -> u64 {
+        match self {
+            SectionContents::Data(data) | SectionContents::Strings(data) => data.len() as u64,
+            SectionContents::Zeroed(len) => *len,
+        }
+    }
+}
+
+impl SectionData {
+    fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            contents: SectionContents::Data(Vec::new()),
+            alignment: 1,
+            writable: false,
+            shared_data: false,
+        }
+    }
+
+    fn typ(&self) -> u32 {
+        match self.contents {
+            SectionContents::Data(_) | SectionContents::Strings(_) => SHT_PROGBITS,
+            SectionContents::Zeroed(_) => SHT_NOBITS,
+        }
+    }
+
+    fn entsize(&self) -> u64 {
+        match self.contents {
+            SectionContents::Strings(_) => 1, // one byte per character
+            _ => 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            None if self.writable => ".data",
+            None => ".rodata",
+        }
+    }
+
+    fn flags(&self) -> u32 {
+        match self.contents {
+            SectionContents::Strings(_) => SHF_MERGE | SHF_STRINGS | SHF_ALLOC,
+            _ if self.writable => SHF_WRITE | SHF_ALLOC,
+            _ => SHF_ALLOC,
+        }
+    }
 }
 
 /// Represents one symbol that's been written already to a [`Builder`].
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Symbol {
+    section: Section,
     rodata_offset: u64,
     size: u64,
     padded_size: u64,
@@ -845,14 +2540,55 @@ const SHT_NULL: u32 = 0;
 const SHT_PROGBITS: u32 = 1;
 const SHT_SYMTAB: u32 = 2;
 const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHT_SYMTAB_SHNDX: u32 = 18;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_TLS: u8 = 6;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+const SHN_ABS: u16 = 0xfff1;
+const SHN_COMMON: u16 = 0xfff2;
+const SHN_XINDEX: u16 = 0xffff;
+
+const EM_386: u16 = 3;
+const EM_ARM: u16 = 40;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+
+const R_386_32: u32 = 1;
+const R_ARM_ABS32: u32 = 2;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_32: u32 = 10;
+const R_AARCH64_ABS64: u32 = 257;
+const R_AARCH64_P32_ABS32: u32 = 1;
+const R_RISCV_32: u32 = 1;
+const R_RISCV_64: u32 = 2;
+
+const SHF_WRITE: u32 = 0x1;
 const SHF_ALLOC: u32 = 0x2;
+const SHF_MERGE: u32 = 0x10;
 const SHF_STRINGS: u32 = 0x20;
+const SHF_INFO_LINK: u32 = 0x40;
+const SHF_TLS: u32 = 0x400;
 
-const SHSTRTAB: &[u8] = b"\x00.shstrtab\x00.strtab\x00.symtab\x00.rodata\x00";
+/// The first section header index used for data sections. Index zero is the
+/// null section and index one is always `.shstrtab`.
+const FIRST_DATA_SECTION_IDX: u16 = 2;
+
+/// The fixed prefix of `.shstrtab`, containing the names of the metadata
+/// sections. The names of the data sections are appended after this.
+const SHSTRTAB: &[u8] = b"\x00.shstrtab\x00.strtab\x00.symtab\x00";
 const SHSTRTAB_SHSTRTAB: u32 = 1;
 const SHSTRTAB_STRTAB: u32 = 11;
 const SHSTRTAB_SYMTAB: u32 = 19;
-const SHSTRTAB_RODATA: u32 = 27;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests;
+
+#[cfg(test)]
+mod core_tests;
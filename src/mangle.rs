@@ -0,0 +1,188 @@
+//! Naming symbols after C++ variables in namespaces, using the name mangling
+//! of the Itanium C++ ABI that GCC and Clang use for ELF targets.
+
+use crate::io::{Error, ErrorKind, Result, Write};
+use crate::{is_c_identifier, Builder, Class};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The fundamental C++ types that the elements of a symbol's data can be
+/// declared as, for use with [`Builder::set_cpp_names`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CppType {
+    /// `char`.
+    Char,
+
+    /// `signed char`.
+    SignedChar,
+
+    /// `unsigned char`.
+    UnsignedChar,
+
+    /// `short`.
+    Short,
+
+    /// `unsigned short`.
+    UnsignedShort,
+
+    /// `int`.
+    Int,
+
+    /// `unsigned int`.
+    UnsignedInt,
+
+    /// `long`, which has the word size of the destination ELF file.
+    Long,
+
+    /// `unsigned long`, which has the word size of the destination ELF file.
+    UnsignedLong,
+
+    /// `long long`.
+    LongLong,
+
+    /// `unsigned long long`.
+    UnsignedLongLong,
+
+    /// `float`.
+    Float,
+
+    /// `double`.
+    Double,
+}
+
+impl CppType {
+    /// Returns the size of the type in bytes for the given ELF class, which
+    /// is also the alignment the builder gives it by default.
+    pub fn size(self, class: Class) -> usize {
+        match self {
+            CppType::Char | CppType::SignedChar | CppType::UnsignedChar => 1,
+            CppType::Short | CppType::UnsignedShort => 2,
+            CppType::Int | CppType::UnsignedInt | CppType::Float => 4,
+            CppType::Long | CppType::UnsignedLong => match class {
+                Class::ELF32 => 4,
+                Class::ELF64 => 8,
+            },
+            CppType::LongLong | CppType::UnsignedLongLong | CppType::Double => 8,
+        }
+    }
+}
+
+impl<W: Write> Builder<W> {
+    /// Enables or disables naming symbols added after this call after C++
+    /// variables, which are arrays of the given element type.
+    ///
+    /// When enabled, each name given to the `add_symbol` functions and each
+    /// [pointer target](crate::RelocatableData::pointer) is a C++ variable
+    /// name that may be qualified with namespaces, such as
+    /// `assets::images::logo`, and the symbol table holds its mangled name,
+    /// `_ZN6assets6images4logoE`. C++ code can then declare the symbol
+    /// directly, without `extern "C"`:
+    ///
+    /// ```c++
+    /// namespace assets::images {
+    /// extern const unsigned char logo[];
+    /// }
+    /// ```
+    ///
+    /// Names that aren't in a namespace aren't mangled, just as C++ compilers
+    /// don't mangle the names of global variables. The names of
+    /// [companion symbols](Self::set_companion_symbols) are mangled after
+    /// applying their templates, so that a template of `{}_end` names a
+    /// variable in the same namespace.
+    ///
+    /// The Itanium C++ ABI doesn't include the types of variables in their
+    /// mangled names, so the element type doesn't affect the names. Instead,
+    /// the `add_symbol` functions return an error of kind
+    /// [`ErrorKind::InvalidInput`] if the size of a symbol's data isn't a
+    /// whole number of elements, and symbols added without an explicit
+    /// alignment are aligned to the size of the element type if that's larger
+    /// than the word size of the destination ELF file.
+    ///
+    /// The `add_symbol` functions return an error wrapping
+    /// [`SymbolNameError::NotCppName`](crate::SymbolNameError::NotCppName)
+    /// for any name that isn't a sequence of valid C identifiers separated by
+    /// `::`. While C++ naming is enabled, [`c_header`](Self::c_header),
+    /// [`cpp_header`](Self::cpp_header) and [`rust_module`](Self::rust_module)
+    /// return errors, because their declarations would use the mangled names.
+    pub fn set_cpp_names(&mut self, element: Option<CppType>) {
+        self.cpp_names = element;
+    }
+
+    /// Returns the name to write to the symbol table for a symbol of the
+    /// given name, which must already have been checked with
+    /// [`is_cpp_name`] if C++ naming is enabled.
+    pub(crate) fn mangled_name(&self, name: &str) -> String {
+        match self.cpp_names {
+            Some(_) => mangle(name),
+            None => String::from(name),
+        }
+    }
+
+    /// Returns the size of the elements of data symbols, which is 1 unless
+    /// C++ naming is enabled.
+    pub(crate) fn element_size(&self) -> u64 {
+        self.cpp_names.map_or(1, |typ| typ.size(self.class) as u64)
+    }
+
+    /// Returns the alignment for data symbols that weren't given an explicit
+    /// alignment.
+    pub(crate) fn default_alignment(&self) -> usize {
+        self.word_size().max(self.element_size() as usize)
+    }
+}
+
+/// Checks that a symbol of the given size holds a whole number of elements
+/// of the given size.
+pub(crate) fn check_element_size(size: u64, element_size: u64) -> Result<()> {
+    if size % element_size != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "symbol size {} is not a multiple of the size of its element type, {}",
+                size, element_size
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns true if the given name is a C++ variable name, optionally
+/// qualified with namespaces, that can be mangled.
+pub(crate) fn is_cpp_name(name: &str) -> bool {
+    let name = name.strip_prefix("::").unwrap_or(name);
+    name.split("::").all(is_c_identifier)
+}
+
+/// Returns the mangled name of a variable with the given qualified name.
+fn mangle(name: &str) -> String {
+    let name = name.strip_prefix("::").unwrap_or(name);
+    let mut parts: Vec<&str> = name.split("::").collect();
+    if parts.len() == 1 {
+        return String::from(name);
+    }
+
+    // Names in the std namespace have an abbreviation, and a variable
+    // directly in std doesn't need the nested-name delimiters.
+    let mut out = String::from("_Z");
+    let nested = if parts[0] == "std" {
+        parts.remove(0);
+        if parts.len() > 1 {
+            out.push_str("NSt");
+            true
+        } else {
+            out.push_str("St");
+            false
+        }
+    } else {
+        out.push('N');
+        true
+    };
+    for part in parts.iter() {
+        out.push_str(&format!("{}{}", part.len(), part));
+    }
+    if nested {
+        out.push('E');
+    }
+    out
+}
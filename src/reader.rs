@@ -0,0 +1,602 @@
+//! Reading ELF object files, such as those produced by [`Builder`](crate::Builder)
+//! or by `objcopy -I binary`.
+//!
+//! ```
+//! # fn main() -> elfbin::io::Result<()> {
+//! # let mut builder = elfbin::Builder::new(
+//! #     elfbin::Header {
+//! #         class: elfbin::Class::ELF64,
+//! #         encoding: elfbin::Encoding::LSB,
+//! #         machine: 62,
+//! #         flags: 0,
+//! #     },
+//! #     Vec::new(),
+//! # )?;
+//! # builder.add_symbol("example", &b"hello!"[..])?;
+//! # let file_bytes = builder.close()?;
+//! use elfbin::io::Read;
+//!
+//! let reader = elfbin::Reader::new(&file_bytes)?;
+//! for sym in reader.symbols() {
+//!     println!("{} is {} bytes", sym.name, sym.size);
+//! }
+//!
+//! let example = reader.symbol("example").unwrap();
+//! let mut data = Vec::new();
+//! reader.symbol_reader(example)?.read_to_end(&mut data)?;
+//! assert_eq!(data, b"hello!");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::io::{Error, ErrorKind, Read, Result};
+use crate::{Binding, Class, Encoding, Header, Visibility};
+use crate::{SHF_TLS, SHN_ABS, SHN_COMMON, SHN_LORESERVE, SHN_UNDEF, SHN_XINDEX};
+use crate::{SHT_NOBITS, SHT_SYMTAB, SHT_SYMTAB_SHNDX, STT_TLS};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A parsed ELF file.
+///
+/// The reader is intended for relocatable object files, but it also accepts
+/// executables and shared libraries. In those the symbol values are memory
+/// addresses, which the reader translates to offsets using the addresses of
+/// the sections. The exception is thread-local (`STT_TLS`) symbols, whose
+/// values are offsets into the thread-local storage block that starts with
+/// the first `SHF_TLS` section, and which the reader translates using that
+/// section's address instead.
+pub struct Reader<'a> {
+    data: &'a [u8],
+    header: Header,
+    file_type: u16,
+    sections: Vec<SectionInfo>,
+    symbols: Vec<SymbolInfo>,
+}
+
+/// Describes a section of an ELF file read by [`Reader`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SectionInfo {
+    /// The index of the section in the section header table.
+    pub index: usize,
+
+    /// The name of the section.
+    pub name: String,
+
+    /// The section type, such as `SHT_PROGBITS` (1) or `SHT_NOBITS` (8).
+    pub typ: u32,
+
+    /// The section flags, such as `SHF_WRITE` (1) and `SHF_ALLOC` (2).
+    pub flags: u64,
+
+    /// The address of the section in memory, which is zero for sections
+    /// of relocatable object files.
+    pub addr: u64,
+
+    /// The offset of the section's contents from the start of the file.
+    pub offset: u64,
+
+    /// The size of the section in bytes.
+    pub size: u64,
+
+    /// The section's `sh_link` field, whose meaning depends on its type.
+    pub link: u32,
+
+    /// The section's `sh_info` field, whose meaning depends on its type.
+    pub info: u32,
+
+    /// The required alignment of the section, or zero or one if it has no
+    /// alignment requirements.
+    pub alignment: u64,
+
+    /// The size of each entry, for sections containing a table of
+    /// fixed-size entries.
+    pub entsize: u64,
+}
+
+impl SectionInfo {
+    /// Returns true if the section occupies no space in the file, because
+    /// it's zero-initialized by the program loader.
+    pub fn is_zeroed(&self) -> bool {
+        self.typ == SHT_NOBITS
+    }
+}
+
+/// Describes a symbol of an ELF file read by [`Reader`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SymbolInfo {
+    /// The name of the symbol.
+    pub name: String,
+
+    /// The symbol's value, which for symbols defined in a section of a
+    /// relocatable object file is its offset within that section.
+    pub value: u64,
+
+    /// The size of the symbol in bytes.
+    pub size: u64,
+
+    /// Where the symbol is defined.
+    pub section: SymbolSection,
+
+    /// The symbol type, such as `STT_OBJECT` (1).
+    pub typ: u8,
+
+    /// The symbol's binding.
+    pub binding: Binding,
+
+    /// The symbol's visibility.
+    pub visibility: Visibility,
+
+    /// The alignment of the symbol, which ELF doesn't record directly and
+    /// so is inferred as the largest power of two that divides the symbol's
+    /// offset, limited to the alignment of its section. For common symbols
+    /// it's the alignment the symbol requires, and for symbols not defined
+    /// in a section it's one.
+    pub alignment: u64,
+}
+
+/// Where a [`SymbolInfo`] is defined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolSection {
+    /// The symbol is defined in some other file.
+    Undefined,
+
+    /// The symbol has an absolute value that isn't relative to any section.
+    Absolute,
+
+    /// The symbol is a common block not yet allocated to a section.
+    Common,
+
+    /// The symbol is defined in the section at the given index of
+    /// [`Reader::sections`].
+    Index(usize),
+
+    /// The symbol's section index is some other reserved value with a
+    /// processor- or operating system-specific meaning, such as
+    /// `SHN_MIPS_ACOMMON` (0xff00).
+    Reserved(u16),
+}
+
+impl<'a> Reader<'a> {
+    /// Parses the ELF file in the given buffer.
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidData`] if the buffer
+    /// doesn't contain a well-formed ELF file.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        if data.len() < 16 || data[0..4] != b"\x7fELF"[..] {
+            return Err(invalid("not an ELF file"));
+        }
+        let class = match data[4] {
+            1 => Class::ELF32,
+            2 => Class::ELF64,
+            _ => return Err(invalid("unsupported ELF class")),
+        };
+        let encoding = match data[5] {
+            1 => Encoding::LSB,
+            2 => Encoding::MSB,
+            _ => return Err(invalid("unsupported ELF encoding")),
+        };
+        let d = Decoder {
+            data,
+            class,
+            encoding,
+        };
+
+        let file_type = d.u16(16)?;
+        let machine = d.u16(18)?;
+        let (shoff, flags, shentsize, shnum, shstrndx) = match class {
+            Class::ELF32 => (
+                d.u32(32)? as u64,
+                d.u32(36)?,
+                d.u16(46)?,
+                d.u16(48)?,
+                d.u16(50)?,
+            ),
+            Class::ELF64 => (d.u64(40)?, d.u32(48)?, d.u16(58)?, d.u16(60)?, d.u16(62)?),
+        };
+        let expected_shentsize = match class {
+            Class::ELF32 => 40,
+            Class::ELF64 => 64,
+        };
+
+        let mut headers: Vec<SectionInfo> = Vec::new();
+        let mut name_idxs: Vec<u32> = Vec::new();
+        if shoff != 0 {
+            if shentsize != expected_shentsize {
+                return Err(invalid("unexpected section header entry size"));
+            }
+            // With extended section numbering, the real count and string
+            // table index are in the first section header.
+            let (first, _) = d.section_header(shoff, 0)?;
+            let count = match shnum {
+                0 => first.size,
+                n => n as u64,
+            };
+            if count > (data.len() as u64) / (shentsize as u64) {
+                return Err(invalid("section header table is outside of the file"));
+            }
+            for i in 0..count as usize {
+                let (sect, name_idx) = d.section_header(shoff + i as u64 * shentsize as u64, i)?;
+                headers.push(sect);
+                name_idxs.push(name_idx);
+            }
+            let shstrndx = match shstrndx {
+                SHN_XINDEX => first.link as usize,
+                n => n as usize,
+            };
+            for sect in headers.iter() {
+                if !sect.is_zeroed() {
+                    d.range(sect.offset, sect.size)?;
+                }
+            }
+            if shstrndx != 0 {
+                let shstrtab = match headers.get(shstrndx) {
+                    Some(sect) if !sect.is_zeroed() => d.range(sect.offset, sect.size)?,
+                    _ => return Err(invalid("invalid section name table index")),
+                };
+                for (sect, name_idx) in headers.iter_mut().zip(name_idxs) {
+                    sect.name = string_at(shstrtab, name_idx)?;
+                }
+            }
+        }
+
+        let mut symbols: Vec<SymbolInfo> = Vec::new();
+        if let Some(symtab) = headers.iter().find(|sect| sect.typ == SHT_SYMTAB) {
+            let strtab = match headers.get(symtab.link as usize) {
+                Some(sect) if !sect.is_zeroed() => d.range(sect.offset, sect.size)?,
+                _ => return Err(invalid("invalid symbol name table index")),
+            };
+            let entsize: u64 = match class {
+                Class::ELF32 => 16,
+                Class::ELF64 => 24,
+            };
+            // Symbols whose section index doesn't fit in their entry have
+            // it in a separate table instead.
+            let xindex = headers
+                .iter()
+                .find(|sect| sect.typ == SHT_SYMTAB_SHNDX && sect.link as usize == symtab.index);
+            // Symbol zero is the null symbol, which we don't report.
+            for i in 1..(symtab.size / entsize) {
+                let sym = d.symbol(symtab.offset + i * entsize, i, strtab, xindex, &headers)?;
+                symbols.push(sym);
+            }
+        }
+
+        Ok(Self {
+            data,
+            header: Header {
+                class,
+                encoding,
+                machine,
+                flags,
+            },
+            file_type,
+            sections: headers,
+            symbols,
+        })
+    }
+
+    /// Returns the values from the ELF header that [`Builder`](crate::Builder)
+    /// accepts.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the ELF file type, which is `ET_REL` (1) for a relocatable
+    /// object file.
+    pub fn file_type(&self) -> u16 {
+        self.file_type
+    }
+
+    /// Returns all of the sections in the file, in section header table
+    /// order, including the null section at index zero.
+    pub fn sections(&self) -> &[SectionInfo] {
+        &self.sections
+    }
+
+    /// Returns the section with the given name, if any.
+    pub fn section(&self, name: &str) -> Option<&SectionInfo> {
+        self.sections.iter().find(|sect| sect.name == name)
+    }
+
+    /// Returns all of the symbols in the symbol table, in symbol table order,
+    /// excluding the null symbol at index zero.
+    pub fn symbols(&self) -> &[SymbolInfo] {
+        &self.symbols
+    }
+
+    /// Returns the first symbol with the given name, if any.
+    pub fn symbol(&self, name: &str) -> Option<&SymbolInfo> {
+        self.symbols.iter().find(|sym| sym.name == name)
+    }
+
+    /// Returns the contents of the given section, or `None` if the section
+    /// is [zeroed](SectionInfo::is_zeroed) and so has no contents in the file.
+    pub fn section_data(&self, sect: &SectionInfo) -> Option<&'a [u8]> {
+        if sect.is_zeroed() {
+            return None;
+        }
+        // The range was checked when we parsed the section headers.
+        let start = sect.offset as usize;
+        Some(&self.data[start..start + sect.size as usize])
+    }
+
+    /// Returns a reader over the bytes of the given symbol, as determined by
+    /// its value and size.
+    ///
+    /// Returns an error of kind [`ErrorKind::InvalidInput`] if the symbol
+    /// isn't defined in a section, or of kind [`ErrorKind::InvalidData`] if
+    /// its bytes extend beyond the end of its section.
+    pub fn symbol_reader(&self, sym: &SymbolInfo) -> Result<SymbolReader<'a>> {
+        let sect = match sym.section {
+            SymbolSection::Index(idx) => &self.sections[idx],
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("symbol {:?} is not defined in a section", sym.name),
+                ))
+            }
+        };
+        let offset = sym
+            .value
+            .wrapping_sub(section_base(&self.sections, sect, sym.typ));
+        if offset > sect.size || sym.size > sect.size - offset {
+            return Err(invalid(format!(
+                "symbol {:?} extends beyond the end of its section",
+                sym.name
+            )));
+        }
+        Ok(match self.section_data(sect) {
+            Some(data) => {
+                let start = offset as usize;
+                SymbolReader::Data(&data[start..start + sym.size as usize])
+            }
+            None => SymbolReader::Zeroed(sym.size),
+        })
+    }
+}
+
+/// A reader over the bytes of a symbol, returned by
+/// [`Reader::symbol_reader`].
+#[derive(Clone, Debug)]
+pub enum SymbolReader<'a> {
+    /// The symbol's bytes are stored in the file.
+    Data(&'a [u8]),
+
+    /// The symbol is in a zeroed section, and so its bytes are the given
+    /// number of zeros.
+    Zeroed(u64),
+}
+
+impl Read for SymbolReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            SymbolReader::Data(data) => data.read(buf),
+            SymbolReader::Zeroed(remain) => {
+                let n = core::cmp::min(buf.len() as u64, *remain) as usize;
+                buf[..n].fill(0);
+                *remain -= n as u64;
+                Ok(n)
+            }
+        }
+    }
+}
+
+/// Reads integers from an ELF file, checking that they are in bounds.
+pub(crate) struct Decoder<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) class: Class,
+    pub(crate) encoding: Encoding,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn range(&self, offset: u64, len: u64) -> Result<&'a [u8]> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.data.len() as u64 => {
+                Ok(&self.data[offset as usize..end as usize])
+            }
+            _ => Err(invalid("file is truncated")),
+        }
+    }
+
+    fn bytes<const N: usize>(&self, offset: u64) -> Result<[u8; N]> {
+        let mut ret = [0_u8; N];
+        ret.copy_from_slice(self.range(offset, N as u64)?);
+        Ok(ret)
+    }
+
+    fn u8(&self, offset: u64) -> Result<u8> {
+        Ok(self.bytes::<1>(offset)?[0])
+    }
+
+    pub(crate) fn u16(&self, offset: u64) -> Result<u16> {
+        let bytes = self.bytes(offset)?;
+        Ok(match self.encoding {
+            Encoding::LSB => u16::from_le_bytes(bytes),
+            Encoding::MSB => u16::from_be_bytes(bytes),
+        })
+    }
+
+    pub(crate) fn u32(&self, offset: u64) -> Result<u32> {
+        let bytes = self.bytes(offset)?;
+        Ok(match self.encoding {
+            Encoding::LSB => u32::from_le_bytes(bytes),
+            Encoding::MSB => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64> {
+        let bytes = self.bytes(offset)?;
+        Ok(match self.encoding {
+            Encoding::LSB => u64::from_le_bytes(bytes),
+            Encoding::MSB => u64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads a field that is 32 bits wide in ELF32 and 64 bits in ELF64.
+    pub(crate) fn word(&self, offset: u64) -> Result<u64> {
+        match self.class {
+            Class::ELF32 => Ok(self.u32(offset)? as u64),
+            Class::ELF64 => self.u64(offset),
+        }
+    }
+
+    /// Reads the section header at the given offset, returning it along with
+    /// the index of its name in the section name table. The name itself is
+    /// left empty for the caller to fill in.
+    fn section_header(&self, offset: u64, index: usize) -> Result<(SectionInfo, u32)> {
+        let (w, flags_at) = match self.class {
+            Class::ELF32 => (4, 8),
+            Class::ELF64 => (8, 8),
+        };
+        let name_idx = self.u32(offset)?;
+        let sect = SectionInfo {
+            index,
+            name: String::new(),
+            typ: self.u32(offset + 4)?,
+            flags: self.word(offset + flags_at)?,
+            addr: self.word(offset + flags_at + w)?,
+            offset: self.word(offset + flags_at + w * 2)?,
+            size: self.word(offset + flags_at + w * 3)?,
+            link: self.u32(offset + flags_at + w * 4)?,
+            info: self.u32(offset + flags_at + w * 4 + 4)?,
+            alignment: self.word(offset + flags_at + w * 4 + 8)?,
+            entsize: self.word(offset + flags_at + w * 5 + 8)?,
+        };
+        Ok((sect, name_idx))
+    }
+
+    /// Reads the symbol at the given offset, which has the given index in
+    /// the symbol table.
+    fn symbol(
+        &self,
+        offset: u64,
+        index: u64,
+        strtab: &[u8],
+        xindex: Option<&SectionInfo>,
+        sections: &[SectionInfo],
+    ) -> Result<SymbolInfo> {
+        let name_idx = self.u32(offset)?;
+        let (value, size, info, other, shndx) = match self.class {
+            Class::ELF32 => (
+                self.u32(offset + 4)? as u64,
+                self.u32(offset + 8)? as u64,
+                self.u8(offset + 12)?,
+                self.u8(offset + 13)?,
+                self.u16(offset + 14)?,
+            ),
+            Class::ELF64 => (
+                self.u64(offset + 8)?,
+                self.u64(offset + 16)?,
+                self.u8(offset + 4)?,
+                self.u8(offset + 5)?,
+                self.u16(offset + 6)?,
+            ),
+        };
+        let name = string_at(strtab, name_idx)?;
+        let binding = match info >> 4 {
+            0 => Binding::Local,
+            1 => Binding::Global,
+            2 => Binding::Weak,
+            other => Binding::Other(other),
+        };
+        let visibility = match other & 0x3 {
+            0 => Visibility::Default,
+            1 => Visibility::Internal,
+            2 => Visibility::Hidden,
+            _ => Visibility::Protected,
+        };
+        let (section, alignment) = match shndx {
+            SHN_UNDEF => (SymbolSection::Undefined, 1),
+            SHN_ABS => (SymbolSection::Absolute, 1),
+            SHN_COMMON => (SymbolSection::Common, value.max(1)),
+            SHN_XINDEX => {
+                let table = match xindex {
+                    Some(table) if (index + 1) * 4 <= table.size => table,
+                    _ => {
+                        return Err(invalid(format!(
+                            "symbol {:?} has no extended section index",
+                            name
+                        )))
+                    }
+                };
+                let idx = self.u32(table.offset + index * 4)?;
+                defined_in(sections, idx as usize, value, info & 0xf, &name)?
+            }
+            idx if idx >= SHN_LORESERVE => (SymbolSection::Reserved(idx), 1),
+            idx => defined_in(sections, idx as usize, value, info & 0xf, &name)?,
+        };
+        Ok(SymbolInfo {
+            name,
+            value,
+            size,
+            section,
+            typ: info & 0xf,
+            binding,
+            visibility,
+            alignment,
+        })
+    }
+}
+
+/// Returns where a symbol with the given value and type is defined, given
+/// the index of its section, along with its alignment.
+fn defined_in(
+    sections: &[SectionInfo],
+    idx: usize,
+    value: u64,
+    typ: u8,
+    name: &str,
+) -> Result<(SymbolSection, u64)> {
+    let sect = match sections.get(idx) {
+        Some(sect) => sect,
+        None => {
+            return Err(invalid(format!(
+                "symbol {:?} refers to a nonexistent section",
+                name
+            )))
+        }
+    };
+    let offset = value.wrapping_sub(section_base(sections, sect, typ));
+    let sect_align = sect.alignment.max(1);
+    let alignment = match offset {
+        0 => sect_align,
+        offset => (1_u64 << offset.trailing_zeros()).min(sect_align),
+    };
+    Ok((SymbolSection::Index(idx), alignment))
+}
+
+/// Returns the value that a symbol of the given type would have at the
+/// start of the given section.
+///
+/// That's the section's address, except for thread-local symbols, whose
+/// values are relative to the start of the thread-local storage block. In
+/// relocatable object files every section's address is zero, and so every
+/// symbol's value is its offset within its section.
+fn section_base(sections: &[SectionInfo], sect: &SectionInfo, typ: u8) -> u64 {
+    if typ == STT_TLS {
+        let first_tls = sections
+            .iter()
+            .find(|sect| sect.flags & SHF_TLS as u64 != 0);
+        if let Some(first_tls) = first_tls {
+            return sect.addr.wrapping_sub(first_tls.addr);
+        }
+    }
+    sect.addr
+}
+
+/// Returns the null-terminated string starting at the given index of a
+/// string table.
+fn string_at(table: &[u8], idx: u32) -> Result<String> {
+    let start = idx as usize;
+    if start >= table.len() && !(start == 0 && table.is_empty()) {
+        return Err(invalid("string table index is out of range"));
+    }
+    let tail = table.get(start..).unwrap_or(&[]);
+    let len = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    Ok(String::from_utf8_lossy(&tail[..len]).into_owned())
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
@@ -0,0 +1,133 @@
+//! Generating Rust modules that declare the symbols of a [`Builder`] along
+//! with safe accessor functions.
+
+use crate::c_header::DeclarationKind;
+use crate::io::{Error, ErrorKind, Result, Write};
+use crate::{is_c_identifier, Builder, SymbolNameError};
+use alloc::format;
+use alloc::string::String;
+
+impl<W: Write> Builder<W> {
+    /// Returns the source code of a Rust module declaring each of the global
+    /// and weak symbols that have been added to the builder so far, with a
+    /// function for each symbol that accesses it without `unsafe` code.
+    ///
+    /// The symbols are declared as `extern` statics in a private submodule
+    /// named `ffi`, with array types whose lengths are the sizes of the
+    /// symbols. The functions have the same names as the symbols:
+    ///
+    /// * Data symbols have functions returning a `&'static [u8]`, unless the
+    ///   symbol is in a writable or zeroed section, in which case the function
+    ///   returns a `*mut [u8; N]` because Rust can't guarantee that nothing
+    ///   else modifies the data.
+    /// * [String symbols](Self::add_string_symbol) have functions returning a
+    ///   `&'static str`, which excludes the null terminator.
+    /// * Symbols that only mark a position, such as the end symbols of
+    ///   [companion symbols](Self::set_companion_symbols), have functions
+    ///   returning a `*const u8`.
+    /// * Absolute symbols have functions returning their value as a `usize`.
+    ///
+    /// The module has no inner attributes at its top level, so it can be
+    /// included into another module with `include!`, such as from a file that
+    /// a build script wrote. It requires Rust 1.82 or later, for
+    /// `unsafe extern` blocks.
+    ///
+    /// Returns an error wrapping [`SymbolNameError::NotCIdentifier`] if any
+    /// symbol name isn't a valid C identifier, or an error of kind
+    /// [`ErrorKind::InvalidInput`] if a symbol name is a Rust keyword or
+    /// [C++ naming](Self::set_cpp_names) is enabled.
+    pub fn rust_module(&self) -> Result<String> {
+        self.check_unmangled("Rust module")?;
+        let decls = self.declarations();
+        for decl in decls.iter() {
+            if !is_c_identifier(decl.name) {
+                return Err(SymbolNameError::NotCIdentifier(String::from(decl.name)).into());
+            }
+            if RUST_KEYWORDS.contains(&decl.name) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the symbol name {} is a Rust keyword", decl.name),
+                ));
+            }
+        }
+
+        let mut out = String::from(
+            "// Generated by elfbin. Do not edit.\n\nmod ffi {\n    #![allow(non_upper_case_globals)]\n\n    unsafe extern \"C\" {\n",
+        );
+        for decl in decls.iter() {
+            let (qualifier, size) = match decl.kind {
+                DeclarationKind::Data if decl.writable => ("mut ", decl.size),
+                DeclarationKind::Data | DeclarationKind::String => ("", decl.size),
+                DeclarationKind::Marker | DeclarationKind::Absolute(_) => ("", 0),
+            };
+            out.push_str(&format!(
+                "        pub static {}{}: [u8; {}];\n",
+                qualifier, decl.name, size
+            ));
+        }
+        out.push_str("    }\n}\n");
+
+        for decl in decls.iter() {
+            let name = decl.name;
+            out.push('\n');
+            out.push_str(&match decl.kind {
+                DeclarationKind::Absolute(value) => format!(
+                    "/// Returns the value of the absolute symbol `{}`, which is {:#x}.\n",
+                    name, value
+                ),
+                DeclarationKind::Data if decl.writable => {
+                    format!(
+                        "/// Returns a pointer to the data of the `{}` symbol.\n",
+                        name
+                    )
+                }
+                DeclarationKind::Data => {
+                    format!("/// Returns the data of the `{}` symbol.\n", name)
+                }
+                DeclarationKind::String => {
+                    format!("/// Returns the string in the `{}` symbol.\n", name)
+                }
+                DeclarationKind::Marker => {
+                    format!("/// Returns the address of the `{}` symbol.\n", name)
+                }
+            });
+            if name.chars().any(|c| c.is_ascii_uppercase()) {
+                out.push_str("#[allow(non_snake_case)]\n");
+            }
+            out.push_str(&match decl.kind {
+                DeclarationKind::Data if decl.writable => format!(
+                    "pub fn {0}() -> *mut [u8; {1}] {{\n    ::core::ptr::addr_of_mut!(ffi::{0})\n}}\n",
+                    name, decl.size
+                ),
+                DeclarationKind::Data => format!(
+                    "pub fn {0}() -> &'static [u8] {{\n    unsafe {{ &ffi::{0} }}\n}}\n",
+                    name
+                ),
+                DeclarationKind::String => format!(
+                    "pub fn {0}() -> &'static str {{\n    unsafe {{ ::core::str::from_utf8_unchecked(&ffi::{0}[..{1}]) }}\n}}\n",
+                    name,
+                    decl.size.saturating_sub(1)
+                ),
+                DeclarationKind::Marker => format!(
+                    "pub fn {0}() -> *const u8 {{\n    ::core::ptr::addr_of!(ffi::{0}) as *const u8\n}}\n",
+                    name
+                ),
+                DeclarationKind::Absolute(_) => format!(
+                    "pub fn {0}() -> usize {{\n    ::core::ptr::addr_of!(ffi::{0}) as usize\n}}\n",
+                    name
+                ),
+            });
+        }
+        Ok(out)
+    }
+}
+
+/// The names that can't be used for the statics and functions in a Rust
+/// module, because they're keywords in some edition of Rust.
+const RUST_KEYWORDS: &[&str] = &[
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
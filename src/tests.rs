@@ -1,5 +1,6 @@
+use super::reader::SymbolSection;
 use super::*;
-use std::io::{Cursor, Result};
+use std::io::{Cursor, Result, Seek};
 
 #[test]
 fn no_symbols_le32() -> Result<()> {
@@ -98,6 +99,7 @@ fn three_symbols_le32() -> Result<()> {
     assert_eq!(
         sym_a,
         Symbol {
+            section: Section(0),
             rodata_offset: 0,
             size: 2,
             padded_size: 2,
@@ -107,6 +109,7 @@ fn three_symbols_le32() -> Result<()> {
     assert_eq!(
         sym_b,
         Symbol {
+            section: Section(0),
             rodata_offset: 4,
             size: 3,
             padded_size: 5,
@@ -116,6 +119,7 @@ fn three_symbols_le32() -> Result<()> {
     assert_eq!(
         sym_c,
         Symbol {
+            section: Section(0),
             rodata_offset: 8,
             size: 3,
             padded_size: 4,
@@ -246,6 +250,7 @@ fn three_symbols_le64() -> Result<()> {
     assert_eq!(
         sym_a,
         Symbol {
+            section: Section(0),
             rodata_offset: 0,
             size: 2,
             padded_size: 2,
@@ -255,6 +260,7 @@ fn three_symbols_le64() -> Result<()> {
     assert_eq!(
         sym_b,
         Symbol {
+            section: Section(0),
             rodata_offset: 8,
             size: 3,
             padded_size: 9,
@@ -264,6 +270,7 @@ fn three_symbols_le64() -> Result<()> {
     assert_eq!(
         sym_c,
         Symbol {
+            section: Section(0),
             rodata_offset: 16,
             size: 3,
             padded_size: 8,
@@ -353,6 +360,7 @@ fn different_section_name_le32() -> Result<()> {
     assert_eq!(
         sym_a,
         Symbol {
+            section: Section(0),
             rodata_offset: 0,
             size: 2,
             padded_size: 2,
@@ -406,3 +414,2191 @@ fn different_section_name_le32() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn multiple_sections_le64() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        cursor,
+    )?;
+    let fonts = builder.add_section(".rodata.fonts");
+    let images = builder.add_section(".rodata.images");
+    let sym_a = builder.add_symbol_in(images, "A", &b"ay"[..]).unwrap();
+    let sym_b = builder.add_symbol("B", &b"bee"[..]).unwrap();
+    let sym_c = builder
+        .add_symbol_align_in(images, "C", 16, &b"see"[..])
+        .unwrap();
+    assert_eq!(
+        sym_a,
+        Symbol {
+            section: images,
+            rodata_offset: 0,
+            size: 2,
+            padded_size: 2,
+            alignment: 8,
+        }
+    );
+    assert_eq!(
+        sym_b,
+        Symbol {
+            section: builder.default_section(),
+            rodata_offset: 0,
+            size: 3,
+            padded_size: 3,
+            alignment: 8,
+        }
+    );
+    assert_eq!(
+        sym_c,
+        Symbol {
+            section: images,
+            rodata_offset: 16,
+            size: 3,
+            padded_size: 17,
+            alignment: 16,
+        }
+    );
+    assert_ne!(fonts, images);
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    assert_eq!(ef.sections.len(), 7, "seven section headers");
+    let section_names: Vec<&str> = ef.sections.iter().map(|s| &s.shdr.name[..]).collect();
+    assert_eq!(
+        section_names,
+        vec![
+            "",
+            ".shstrtab",
+            ".rodata",
+            ".rodata.fonts",
+            ".rodata.images",
+            ".strtab",
+            ".symtab",
+        ]
+    );
+    let rodata = ef.get_section(".rodata").unwrap();
+    let fonts = ef.get_section(".rodata.fonts").unwrap();
+    let images = ef.get_section(".rodata.images").unwrap();
+    assert_eq!(rodata.data, b"bee".to_vec());
+    assert_eq!(fonts.data.len(), 0);
+    assert_eq!(images.shdr.addralign, 16);
+    assert_eq!(
+        images.shdr.offset % 16,
+        0,
+        "section body is aligned in the file"
+    );
+
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    assert_eq!(
+        syms.len(),
+        4,
+        "three symbols in addition to the zero placeholder"
+    );
+    {
+        // Real symbol 1: A
+        assert_eq!(syms[1].name, "A");
+        assert_eq!(syms[1].shndx, 4);
+        assert_eq!(syms[1].value, 0);
+        let start_offset = syms[1].value as usize;
+        let end_offset = start_offset + syms[1].size as usize;
+        assert_eq!(&images.data[start_offset..end_offset], &b"ay"[..]);
+    }
+    {
+        // Real symbol 2: B
+        assert_eq!(syms[2].name, "B");
+        assert_eq!(syms[2].shndx, 2);
+        assert_eq!(syms[2].value, 0);
+        assert_eq!(syms[2].size, 3);
+    }
+    {
+        // Real symbol 3: C
+        assert_eq!(syms[3].name, "C");
+        assert_eq!(syms[3].shndx, 4);
+        assert_eq!(syms[3].value, 16);
+        let start_offset = syms[3].value as usize;
+        let end_offset = start_offset + syms[3].size as usize;
+        assert_eq!(&images.data[start_offset..end_offset], &b"see"[..]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn foreign_section() -> Result<()> {
+    let hdr = || Header {
+        class: Class::ELF64,
+        encoding: Encoding::LSB,
+        machine: 62, // x86_64
+        flags: 0,
+    };
+    let mut other = Builder::new(hdr(), Vec::new())?;
+    other.add_section(".rodata.fonts");
+    other.add_section(".rodata.images");
+    let images = other.add_section(".rodata.more_images");
+    let bss = other.add_zeroed_section(".bss.buffers");
+    let strings = other.add_string_section(".rodata.str1.1");
+    let sym = other.add_symbol_in(images, "A", &b"ay"[..])?;
+
+    let mut builder = Builder::new(hdr(), Vec::new())?;
+    let errs = vec![
+        builder.set_section_writable(images, true).unwrap_err(),
+        builder.add_symbol_in(images, "B", &b"bee"[..]).unwrap_err(),
+        builder
+            .add_symbol_align_in(images, "B", 16, &b"bee"[..])
+            .unwrap_err(),
+        builder.add_zeroed_symbol_in(bss, "B", 4, 4).unwrap_err(),
+        builder
+            .add_string_symbol_in(strings, "B", "bee")
+            .unwrap_err(),
+        builder
+            .add_filesystem_with(
+                "B",
+                SymbolOptions {
+                    section: Some(images),
+                    ..SymbolOptions::default()
+                },
+                &Filesystem::new(),
+            )
+            .unwrap_err(),
+        builder
+            .add_relocatable_symbol_with(
+                "B",
+                SymbolOptions {
+                    section: Some(images),
+                    ..SymbolOptions::default()
+                },
+                &RelocatableData::new(),
+            )
+            .unwrap_err(),
+        builder.add_symbol_alias(sym, "B").unwrap_err(),
+    ];
+    for err in errs {
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    builder.add_symbol("B", &b"bee"[..])?;
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.sections().len(), 5);
+    assert_eq!(reader.symbols().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn writable_section_le32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    builder.set_section_writable(builder.default_section(), true)?;
+    let cfg = builder.add_section(".flash_cfg");
+    builder.set_section_writable(cfg, true)?;
+    builder.add_symbol("A", &b"ay"[..]).unwrap();
+    builder.add_symbol_in(cfg, "B", &b"bee"[..]).unwrap();
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    assert_eq!(ef.sections.len(), 6, "six section headers");
+    assert!(ef.get_section(".rodata").is_none());
+    let data = ef.get_section(".data").unwrap();
+    assert_eq!(data.shdr.flags.0, 0x3, "SHF_WRITE | SHF_ALLOC");
+    assert_eq!(&data.data[..], &b"ay"[..]);
+    let cfg = ef.get_section(".flash_cfg").unwrap();
+    assert_eq!(cfg.shdr.flags.0, 0x3, "SHF_WRITE | SHF_ALLOC");
+    assert_eq!(&cfg.data[..], &b"bee"[..]);
+
+    Ok(())
+}
+
+#[test]
+fn writable_ignored_for_strings_and_zeroed_le32() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        Vec::new(),
+    )?;
+    let strings = builder.default_string_section();
+    builder.set_section_writable(strings, true)?;
+    builder.add_string_symbol("hello", "Hello")?;
+    let zeroed = builder.default_zeroed_section();
+    builder.set_section_writable(zeroed, false)?;
+    builder.add_zeroed_symbol("fb", 16, 4)?;
+    let header = builder.c_header("ASSETS_H")?;
+    assert!(header.contains("extern const char hello[];"), "{}", header);
+    assert!(header.contains("extern uint8_t fb[] "), "{}", header);
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(
+        reader.section(".rodata.str1.1").unwrap().flags,
+        0x32,
+        "SHF_MERGE | SHF_STRINGS | SHF_ALLOC"
+    );
+    assert_eq!(
+        reader.section(".bss").unwrap().flags,
+        0x3,
+        "SHF_WRITE | SHF_ALLOC"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn zeroed_symbols_be32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::MSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    builder.add_symbol("A", &b"ay"[..]).unwrap();
+    let sym_fb = builder
+        .add_zeroed_symbol("FB", 2 * 1024 * 1024, 64)
+        .unwrap();
+    let sym_ring = builder.add_zeroed_symbol("RING", 100, 32).unwrap();
+    let bss = builder.default_zeroed_section();
+    assert_eq!(
+        sym_fb,
+        Symbol {
+            section: bss,
+            rodata_offset: 0,
+            size: 2 * 1024 * 1024,
+            padded_size: 2 * 1024 * 1024,
+            alignment: 64,
+        }
+    );
+    assert_eq!(
+        sym_ring,
+        Symbol {
+            section: bss,
+            rodata_offset: 2 * 1024 * 1024,
+            size: 100,
+            padded_size: 100,
+            alignment: 32,
+        }
+    );
+    assert!(builder.add_symbol_in(bss, "B", &b"bee"[..]).is_err());
+    let rodata = builder.default_section();
+    assert!(builder.add_zeroed_symbol_in(rodata, "C", 4, 4).is_err());
+
+    let mut cursor = builder.close()?;
+    assert!(
+        cursor.get_ref().len() < 1024,
+        "zeroed data is not in the file"
+    );
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    assert_eq!(ef.sections.len(), 6, "six section headers");
+    let bss = ef.get_section(".bss").unwrap();
+    assert_eq!(bss.shdr.shtype, elf::types::SHT_NOBITS);
+    assert_eq!(bss.shdr.flags.0, 0x3, "SHF_WRITE | SHF_ALLOC");
+    assert_eq!(bss.shdr.size, 2 * 1024 * 1024 + 100);
+    assert_eq!(bss.shdr.addralign, 64);
+
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    assert_eq!(syms.len(), 4);
+    assert_eq!(syms[2].name, "FB");
+    assert_eq!(syms[2].shndx, 3);
+    assert_eq!(syms[3].name, "RING");
+    assert_eq!(syms[3].shndx, 3);
+    assert_eq!(syms[3].value, 2 * 1024 * 1024);
+    assert_eq!(syms[3].size, 100);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_alignment() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::MSB,
+            machine: 0,
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    for &alignment in [0, 3, 12].iter() {
+        let err = builder
+            .add_zeroed_symbol("FB", 16, alignment)
+            .expect_err("zeroed symbol");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let err = builder
+            .add_symbol_align("LOGO", alignment, &b"logo"[..])
+            .expect_err("symbol");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    builder.add_zeroed_symbol("FB", 16, 16)?;
+    builder.add_symbol_align("LOGO", 1, &b"logo"[..])?;
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.section(".rodata").unwrap().size, 4);
+    assert_eq!(reader.symbols().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn zeroed_symbol_too_large() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 0x3e, // x86-64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.add_zeroed_symbol("A", 1, 1)?;
+    let err = builder
+        .add_zeroed_symbol("B", u64::MAX, 8)
+        .expect_err("overflowing section");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.add_zeroed_symbol("C", 7, 8)?;
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.section(".bss").unwrap().size, 15);
+
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        Vec::new(),
+    )?;
+    builder.set_section_per_symbol(true);
+    let err = builder
+        .add_zeroed_symbol("BIG", 5 << 30, 8)
+        .expect_err("symbol larger than 4 GiB");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.set_section_per_symbol(false);
+    builder.add_zeroed_symbol("HALF", 2 << 30, 8)?;
+    let err = builder
+        .add_zeroed_symbol("OTHER_HALF", 2 << 30, 8)
+        .expect_err("section larger than 4 GiB");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert!(reader.section(".bss.BIG").is_none());
+    assert_eq!(reader.section(".bss").unwrap().size, 2 << 30);
+    assert_eq!(reader.symbols().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn failed_read_le32() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        Vec::new(),
+    )?;
+    builder.add_symbol("A", &b"a"[..])?;
+    let err = builder
+        .add_symbol("B", FailingReader(&b"partial"[..]))
+        .expect_err("failing reader");
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    // Neither the padding nor the partial data is left in the section, and
+    // the name can be used again.
+    let b = builder.add_symbol("B", &b"b"[..])?;
+    assert_eq!(b.rodata_offset, 4);
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.section(".rodata").unwrap().size, 5);
+    Ok(())
+}
+
+#[test]
+fn symbol_binding_and_visibility_le64() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        cursor,
+    )?;
+    builder.add_symbol("A", &b"ay"[..]).unwrap();
+    builder
+        .add_symbol_with(
+            "B",
+            SymbolOptions {
+                binding: Binding::Weak,
+                visibility: Visibility::Hidden,
+                ..SymbolOptions::default()
+            },
+            &b"bee"[..],
+        )
+        .unwrap();
+    builder
+        .add_symbol_with(
+            "C",
+            SymbolOptions {
+                binding: Binding::Local,
+                ..SymbolOptions::default()
+            },
+            &b"see"[..],
+        )
+        .unwrap();
+    builder
+        .add_zeroed_symbol_with(
+            "D",
+            16,
+            SymbolOptions {
+                visibility: Visibility::Protected,
+                ..SymbolOptions::default()
+            },
+        )
+        .unwrap();
+    builder
+        .add_symbol_with(
+            "E",
+            SymbolOptions {
+                binding: Binding::Other(10), // STB_GNU_UNIQUE
+                ..SymbolOptions::default()
+            },
+            &b"ee"[..],
+        )
+        .unwrap();
+    // Other bindings must be at most 15, and mustn't alias the named ones.
+    for &value in [16, 0, 1, 2].iter() {
+        let err = builder
+            .add_symbol_with(
+                "F",
+                SymbolOptions {
+                    binding: Binding::Other(value),
+                    ..SymbolOptions::default()
+                },
+                &b"ef"[..],
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let symtab = ef.get_section(".symtab").unwrap();
+    assert_eq!(symtab.shdr.info, 2, "first global symbol follows the local");
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u8, u8)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.bind.0, sym.vis.0))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", elf::types::STB_LOCAL.0, elf::types::STV_DEFAULT.0),
+            ("C", elf::types::STB_LOCAL.0, elf::types::STV_DEFAULT.0),
+            ("A", elf::types::STB_GLOBAL.0, elf::types::STV_DEFAULT.0),
+            ("B", elf::types::STB_WEAK.0, elf::types::STV_HIDDEN.0),
+            ("D", elf::types::STB_GLOBAL.0, elf::types::STV_PROTECTED.0),
+            ("E", 10, elf::types::STV_DEFAULT.0),
+        ]
+    );
+    assert_eq!(syms[1].value, 16, "C keeps its own data");
+    assert_eq!(syms[1].symtype.0, elf::types::STT_OBJECT.0);
+
+    Ok(())
+}
+
+#[test]
+fn companion_symbols_le32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    builder.add_symbol("A", &b"ay"[..]).unwrap();
+    builder.set_companion_symbols(Some(CompanionNaming::objcopy()))?;
+    builder.add_symbol("foo", &b"bee"[..]).unwrap();
+    builder.set_companion_symbols(Some(CompanionNaming {
+        start: String::from("{}"),
+        end: String::from("{}_end"),
+        size: String::from("{}_len"),
+    }))?;
+    builder.add_zeroed_symbol("buf", 100, 4).unwrap();
+    let err = builder
+        .set_companion_symbols(Some(CompanionNaming {
+            start: String::from("s"),
+            ..CompanionNaming::objcopy()
+        }))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.add_symbol("bar", &b"bar"[..]).unwrap();
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u64, u64, u16)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.value, sym.size, sym.shndx))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0, 0, 0),
+            ("A", 0, 2, 2),
+            ("_binary_foo_start", 4, 3, 2),
+            ("_binary_foo_end", 7, 0, 2),
+            ("_binary_foo_size", 3, 0, 0xfff1),
+            ("buf", 0, 100, 3),
+            ("buf_end", 100, 0, 3),
+            ("buf_len", 100, 0, 0xfff1),
+            ("bar", 8, 3, 2),
+            ("bar_end", 11, 0, 2),
+            ("bar_len", 3, 0, 0xfff1),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn absolute_symbols_be64() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::MSB,
+            machine: 0x2b, // SPARC V9
+            flags: 0,
+        },
+        cursor,
+    )?;
+    builder.add_symbol("A", &b"ay"[..]).unwrap();
+    builder.add_absolute_symbol("ASSET_COUNT", 1, None).unwrap();
+    builder
+        .add_absolute_symbol("BIG", 0x1_0000_0000, Some(4))
+        .unwrap();
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u64, u64, u16)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.value, sym.size, sym.shndx))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0, 0, 0),
+            ("A", 0, 2, 2),
+            ("ASSET_COUNT", 1, 0, 0xfff1),
+            ("BIG", 0x1_0000_0000, 4, 0xfff1),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn absolute_symbol_too_large_le32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    builder.add_absolute_symbol("SMALL", 0xffff_ffff, None)?;
+    let err = builder
+        .add_absolute_symbol("BIG", 0x1_0000_0000, None)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[test]
+fn symbol_aliases_le64() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        cursor,
+    )?;
+    builder.add_symbol("A", &b"ay"[..]).unwrap();
+    let logo = builder.add_symbol("logo", &b"bee"[..]).unwrap();
+    let alias = builder
+        .add_symbol_alias(logo, "legacy_logo_bitmap")
+        .unwrap();
+    assert_eq!(alias, logo);
+    builder
+        .add_symbol_alias_with(
+            logo,
+            "logo_weak",
+            SymbolOptions {
+                binding: Binding::Weak,
+                ..SymbolOptions::default()
+            },
+        )
+        .unwrap();
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let rodata = ef.get_section(".rodata").unwrap();
+    assert_eq!(rodata.data.len(), 11, "aliases don't duplicate data");
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u64, u64, u16, u8)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.value, sym.size, sym.shndx, sym.bind.0))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0, 0, 0, 0),
+            ("A", 0, 2, 2, 1),
+            ("logo", 8, 3, 2, 1),
+            ("legacy_logo_bitmap", 8, 3, 2, 1),
+            ("logo_weak", 8, 3, 2, 2),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn invalid_symbol_names() -> Result<()> {
+    fn name_error(err: std::io::Error) -> SymbolNameError {
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<SymbolNameError>())
+            .cloned()
+            .expect("error should wrap a SymbolNameError")
+    }
+
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        cursor,
+    )?;
+    let a = builder.add_symbol("A", &b"ay"[..])?;
+
+    let err = builder.add_symbol("A", &b"bee"[..]).unwrap_err();
+    assert_eq!(name_error(err), SymbolNameError::Duplicate("A".into()));
+    let err = builder.add_symbol("", &b"bee"[..]).unwrap_err();
+    assert_eq!(name_error(err), SymbolNameError::Empty);
+    let err = builder.add_symbol("B\0C", &b"bee"[..]).unwrap_err();
+    assert_eq!(name_error(err), SymbolNameError::ContainsNul("B\0C".into()));
+    let err = builder.add_symbol_alias(a, "A").unwrap_err();
+    assert_eq!(name_error(err), SymbolNameError::Duplicate("A".into()));
+    let err = builder.add_zeroed_symbol("A", 4, 4).unwrap_err();
+    assert_eq!(name_error(err), SymbolNameError::Duplicate("A".into()));
+    let err = builder.add_absolute_symbol("A", 4, None).unwrap_err();
+    assert_eq!(name_error(err), SymbolNameError::Duplicate("A".into()));
+
+    builder.add_symbol("foo.png", &b"bee"[..])?;
+    builder.set_require_c_identifiers(true);
+    let err = builder.add_symbol("bar.png", &b"bee"[..]).unwrap_err();
+    assert_eq!(
+        name_error(err),
+        SymbolNameError::NotCIdentifier("bar.png".into())
+    );
+    let err = builder.add_symbol("1bar", &b"bee"[..]).unwrap_err();
+    assert_eq!(
+        name_error(err),
+        SymbolNameError::NotCIdentifier("1bar".into())
+    );
+
+    builder.set_companion_symbols(Some(CompanionNaming::objcopy()))?;
+    builder.add_symbol("bar", &b"bee"[..])?;
+    let err = builder
+        .add_absolute_symbol("_binary_bar_size", 4, None)
+        .unwrap_err();
+    assert_eq!(
+        name_error(err),
+        SymbolNameError::Duplicate("_binary_bar_size".into())
+    );
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let rodata = ef.get_section(".rodata").unwrap();
+    assert_eq!(rodata.data.len(), 19, "rejected symbols wrote no data");
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let names: Vec<&str> = syms.iter().map(|sym| &sym.name[..]).collect();
+    assert_eq!(
+        names,
+        vec![
+            "",
+            "A",
+            "foo.png",
+            "_binary_bar_start",
+            "_binary_bar_end",
+            "_binary_bar_size",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn deduplicate_le32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    builder.set_deduplicate(true);
+    let icon = builder.add_symbol("icon", &b"icon"[..])?;
+    builder.add_symbol("other", &b"other"[..])?;
+    let icon_copy = builder.add_symbol("icon_copy", &b"icon"[..])?;
+    assert_eq!(
+        icon_copy,
+        Symbol {
+            section: Section(0),
+            rodata_offset: 0,
+            size: 4,
+            padded_size: 0,
+            alignment: 4,
+        }
+    );
+    assert_eq!(icon_copy.rodata_offset, icon.rodata_offset);
+
+    // A copy that needs stricter alignment than the original can't share it.
+    let aligned = builder.add_symbol_align("other_aligned", 16, &b"other"[..])?;
+    assert_eq!(aligned.rodata_offset, 16);
+
+    // Writable data is never shared.
+    let data = builder.add_section(".data");
+    builder.set_section_writable(data, true)?;
+    builder.add_symbol_in(data, "icon_rw", &b"icon"[..])?;
+    builder.add_symbol_in(data, "icon_rw_copy", &b"icon"[..])?;
+
+    // Nor can a section become writable once some of its data is shared.
+    let err = builder
+        .set_section_writable(builder.default_section(), true)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(builder.deduplicated_bytes(), 4);
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let rodata = ef.get_section(".rodata").unwrap();
+    assert_eq!(&rodata.data[..], &b"iconother       other"[..]);
+    let data = ef.get_section(".data").unwrap();
+    assert_eq!(&data.data[..], &b"iconicon"[..]);
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u64, u64, u16)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.value, sym.size, sym.shndx))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0, 0, 0),
+            ("icon", 0, 4, 2),
+            ("other", 4, 5, 2),
+            ("icon_copy", 0, 4, 2),
+            ("other_aligned", 16, 5, 2),
+            ("icon_rw", 0, 4, 3),
+            ("icon_rw_copy", 4, 4, 3),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn section_per_symbol_le64() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        cursor,
+    )?;
+    let shared = builder.add_section(".shared");
+    builder.set_section_per_symbol(true);
+    let logo = builder.add_symbol("logo", &b"logo"[..])?;
+    let font = builder.add_symbol("font", &b"font"[..])?;
+    builder.add_symbol_in(shared, "A", &b"ay"[..])?;
+    builder.add_symbol_in(shared, "B", &b"bee"[..])?;
+    builder.add_zeroed_symbol("fb", 64, 8)?;
+    assert_ne!(logo.section, font.section);
+    assert_eq!(font.rodata_offset, 0);
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let section_names: Vec<&str> = ef.sections.iter().map(|s| &s.shdr.name[..]).collect();
+    assert_eq!(
+        section_names,
+        vec![
+            "",
+            ".shstrtab",
+            ".rodata",
+            ".shared",
+            ".rodata.logo",
+            ".rodata.font",
+            ".bss.fb",
+            ".strtab",
+            ".symtab",
+        ]
+    );
+    assert_eq!(
+        &ef.get_section(".rodata.font").unwrap().data[..],
+        &b"font"[..]
+    );
+    assert_eq!(ef.get_section(".bss.fb").unwrap().shdr.size, 64);
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u16)> = syms.iter().map(|sym| (&sym.name[..], sym.shndx)).collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0),
+            ("logo", 4),
+            ("font", 5),
+            ("A", 3),
+            ("B", 3),
+            ("fb", 6),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn section_per_symbol_template_le32() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        Vec::new(),
+    )?;
+    builder.set_section_per_symbol(true);
+    let err = builder
+        .set_symbol_section_template(Some(String::from(".rodata.assets")))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.set_symbol_section_template(Some(String::from(".rodata.assets.{}")))?;
+    builder.add_symbol("logo", &b"logo"[..])?;
+    builder.add_zeroed_symbol("fb", 64, 8)?;
+
+    // A symbol that can't be added mustn't leave an empty section behind.
+    let err = builder
+        .add_symbol("broken", FailingReader(&b"partial"[..]))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    let section_names: Vec<&str> = reader.sections().iter().map(|s| &s.name[..]).collect();
+    assert_eq!(
+        section_names,
+        vec![
+            "",
+            ".shstrtab",
+            ".rodata",
+            ".rodata.assets.logo",
+            ".bss.fb",
+            ".strtab",
+            ".symtab",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn too_many_sections() -> Result<()> {
+    let header = || Header {
+        class: Class::ELF64,
+        encoding: Encoding::LSB,
+        machine: 62, // x86_64
+        flags: 0,
+    };
+
+    // With section per symbol, the symbol that would need a section index
+    // in the reserved range is rejected.
+    let mut builder = Builder::new(header(), Vec::new())?;
+    builder.set_section_per_symbol(true);
+    let mut count = 0;
+    let err = loop {
+        match builder.add_symbol(format!("s{}", count), &b"x"[..]) {
+            Ok(_) => count += 1,
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(count, 65274);
+    let buf = builder.close()?;
+    let reader = Reader::new(&buf)?;
+    assert_eq!(reader.sections().len(), 65279);
+    assert_eq!(reader.symbols().len(), 65274);
+
+    // Sections declared explicitly are counted when the file is closed.
+    let mut builder = Builder::new(header(), Vec::new())?;
+    for i in 0..65275 {
+        builder.add_section(format!(".s{}", i));
+    }
+    let err = builder.close().err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+fn string_symbols_le32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    builder.add_symbol("A", &b"ay"[..])?;
+    let hello = builder.add_string_symbol("hello", "Hello")?;
+    let world = builder.add_string_symbol("world", "world")?;
+    assert_eq!(
+        hello,
+        Symbol {
+            section: Section(1),
+            rodata_offset: 0,
+            size: 6,
+            padded_size: 6,
+            alignment: 1,
+        }
+    );
+    assert_eq!(world.rodata_offset, 6);
+    assert!(builder.add_string_symbol("nul", "a\0b").is_err());
+    let strings = builder.default_string_section();
+    assert!(builder.add_symbol_in(strings, "B", &b"bee"[..]).is_err());
+    let rodata = builder.default_section();
+    assert!(builder.add_string_symbol_in(rodata, "C", "see").is_err());
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let strings = ef.get_section(".rodata.str1.1").unwrap();
+    assert_eq!(
+        strings.shdr.flags.0, 0x32,
+        "SHF_MERGE | SHF_STRINGS | SHF_ALLOC"
+    );
+    assert_eq!(strings.shdr.entsize, 1);
+    assert_eq!(strings.shdr.addralign, 1);
+    assert_eq!(&strings.data[..], &b"Hello\0world\0"[..]);
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u64, u64, u16)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.value, sym.size, sym.shndx))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0, 0, 0),
+            ("A", 0, 2, 2),
+            ("hello", 0, 6, 3),
+            ("world", 6, 6, 3),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn relocations_le64() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 0x3e, // x86-64 instruction set
+            flags: 0,
+        },
+        cursor,
+    )?;
+    builder.add_symbol("logo", &b"logo"[..])?;
+    let mut table = RelocatableData::new();
+    table.pointer("logo", 0).word(4);
+    table.pointer("font", 2).word(8);
+    let assets = builder.add_relocatable_symbol("assets", &table)?;
+    assert_eq!(assets.rodata_offset, 8);
+    assert_eq!(assets.size, 32);
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let rodata = ef.get_section(".rodata").unwrap();
+    assert_eq!(
+        &rodata.data[..],
+        &b"logo    \0\0\0\0\0\0\0\0\x04\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x08\0\0\0\0\0\0\0"[..]
+    );
+    let symtab = ef.get_section(".symtab").unwrap();
+    let syms = ef.get_symbols(symtab).unwrap();
+    let summary: Vec<(&str, u64, u64, u16)> = syms
+        .iter()
+        .map(|sym| (&sym.name[..], sym.value, sym.size, sym.shndx))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("", 0, 0, 0),
+            ("logo", 0, 4, 2),
+            ("assets", 8, 32, 2),
+            ("font", 0, 0, 0), // undefined
+        ]
+    );
+
+    let rela = ef.get_section(".rela.rodata").unwrap();
+    assert_eq!(rela.shdr.shtype.0, 4, "SHT_RELA");
+    assert_eq!(rela.shdr.flags.0, 0x40, "SHF_INFO_LINK");
+    assert_eq!(rela.shdr.info, 2, "relocates .rodata");
+    assert_eq!(rela.shdr.link, 4, "uses .symtab");
+    assert_eq!(rela.shdr.entsize, 24);
+    assert_eq!(
+        &rela.data[..],
+        &[
+            // r_offset, r_info (symbol 1, R_X86_64_64), r_addend
+            8, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            // r_offset, r_info (symbol 3, R_X86_64_64), r_addend
+            24, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0,
+        ][..]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn relocations_be32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::MSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    let mut table = RelocatableData::new();
+    table.bytes(b"ab").bytes(b"\0\0").pointer("text", 1);
+    builder.add_relocatable_symbol("table", &table)?;
+    builder.add_symbol("text", &b"text"[..])?;
+    let unaligned = SymbolOptions {
+        alignment: Some(2),
+        ..SymbolOptions::default()
+    };
+    assert!(builder
+        .add_relocatable_symbol_with("unaligned", unaligned, &table)
+        .is_err());
+    let zero_aligned = SymbolOptions {
+        alignment: Some(0),
+        ..SymbolOptions::default()
+    };
+    assert!(builder
+        .add_relocatable_symbol_with("unaligned", zero_aligned, &table)
+        .is_err());
+
+    // The addend must fit in the 32-bit field of the data.
+    for &addend in [1 << 31, -(1 << 31) - 1].iter() {
+        let mut table = RelocatableData::new();
+        table.pointer("text", addend);
+        let err = builder
+            .add_relocatable_symbol("far", &table)
+            .expect_err("addend out of range");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    // The targets must be valid symbol names.
+    for (target, expected) in [
+        ("", SymbolNameError::Empty),
+        (
+            "te\0xt",
+            SymbolNameError::ContainsNul(String::from("te\0xt")),
+        ),
+    ]
+    .iter()
+    {
+        let mut table = RelocatableData::new();
+        table.pointer(*target, 0);
+        let err = builder
+            .add_relocatable_symbol("bad", &table)
+            .expect_err("invalid target");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<SymbolNameError>(),
+            Some(expected)
+        );
+    }
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let rodata = ef.get_section(".rodata").unwrap();
+    // With REL-style relocations the addend is stored in the data.
+    assert_eq!(&rodata.data[..], &b"ab\0\0\0\0\0\x01text"[..]);
+    let rel = ef.get_section(".rel.rodata").unwrap();
+    assert_eq!(rel.shdr.shtype.0, 9, "SHT_REL");
+    assert_eq!(rel.shdr.entsize, 8);
+    assert_eq!(
+        &rel.data[..],
+        // r_offset, r_info (symbol 2, R_ARM_ABS32)
+        &[0, 0, 0, 4, 0, 0, 2, 2][..]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn relocations_unsupported_machine() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 0x08, // MIPS
+            flags: 0,
+        },
+        cursor,
+    )?;
+    let mut table = RelocatableData::new();
+    table.word(1);
+    builder.add_relocatable_symbol("words", &table)?;
+    table.pointer("words", 0);
+    let err = builder
+        .add_relocatable_symbol("pointers", &table)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+    Ok(())
+}
+
+#[test]
+fn filesystem_be32() -> Result<()> {
+    let buf: Vec<u8> = Vec::new();
+    let cursor = Cursor::new(buf);
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::MSB,
+            machine: 0x28,     // ARM instruction set
+            flags: 0x05000000, // ARM ABI version 5
+        },
+        cursor,
+    )?;
+    let mut fs = Filesystem::new();
+    fs.add_file("b.txt", &b"bee"[..])?;
+    fs.add_file("a.txt", &b"ay"[..])?;
+    assert!(fs.add_file("a.txt", &b"again"[..]).is_err());
+    assert!(fs.add_file("", &b"empty"[..]).is_err());
+    assert!(fs.add_file("nul\0", &b"nul"[..]).is_err());
+    builder.add_symbol("before", &b"x"[..])?;
+    for &alignment in [0, 4, 24].iter() {
+        let opts = SymbolOptions {
+            alignment: Some(alignment),
+            ..SymbolOptions::default()
+        };
+        let err = builder
+            .add_filesystem_with("assets", opts, &fs)
+            .expect_err("misaligned filesystem");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    let root = builder.add_filesystem("assets", &fs)?;
+    assert_eq!(root.rodata_offset, 8);
+    assert_eq!(root.alignment, 8);
+
+    let mut cursor = builder.close()?;
+    cursor.seek(std::io::SeekFrom::Start(0))?;
+
+    let ef = elf::File::open_stream(&mut cursor).unwrap();
+    let rodata = ef.get_section(".rodata").unwrap();
+    assert_eq!(rodata.shdr.addralign, 8);
+    assert_eq!(
+        &rodata.data[8..],
+        &b"EBFS\
+           \0\0\0\x02\
+           \0\0\0\x20\0\0\0\x30\0\0\0\x02\
+           \0\0\0\x26\0\0\0\x38\0\0\0\x03\
+           a.txt\0b.txt\0\0\0\0\0\
+           ay\0\0\0\0\0\0\
+           bee"[..]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn non_seek_writer_le64() -> Result<()> {
+    let hdr = || Header {
+        class: Class::ELF64,
+        encoding: Encoding::LSB,
+        machine: 62, // x86_64
+        flags: 0,
+    };
+
+    // Vec<u8> implements Write but not Seek.
+    let mut builder = Builder::new(hdr(), Vec::new())?;
+    builder.add_symbol("A", &b"ay"[..])?;
+    let streamed = builder.close()?;
+
+    let mut builder = Builder::new(hdr(), Cursor::new(Vec::new()))?;
+    builder.add_symbol("A", &b"ay"[..])?;
+    let seeked = builder.close()?.into_inner();
+
+    assert_eq!(streamed, seeked);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_builder_matches_sync_le32() -> Result<()> {
+    let hdr = || Header {
+        class: Class::ELF32,
+        encoding: Encoding::LSB,
+        machine: 0x28,     // ARM instruction set
+        flags: 0x05000000, // ARM ABI version 5
+    };
+
+    let mut builder = Builder::new(hdr(), Vec::new())?;
+    builder.set_companion_symbols(Some(CompanionNaming::objcopy()))?;
+    let fonts = builder.add_section(".rodata.fonts");
+    builder.add_symbol("A", &b"ay"[..])?;
+    builder.add_symbol_align_in(fonts, "B", 16, &b"bee"[..])?;
+    builder.add_absolute_symbol("COUNT", 2, None)?;
+    let sync = builder.close()?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let async_ = runtime.block_on(async {
+        let mut builder = AsyncBuilder::new(hdr(), Vec::new())?;
+        builder
+            .builder_mut()
+            .set_companion_symbols(Some(CompanionNaming::objcopy()))?;
+        let fonts = builder.builder_mut().add_section(".rodata.fonts");
+        builder.add_symbol("A", &b"ay"[..]).await?;
+        builder
+            .add_symbol_align_in(fonts, "B", 16, &b"bee"[..])
+            .await?;
+        let partial = tokio::io::AsyncReadExt::chain(&b"partial"[..], FailingAsyncReader);
+        let err = builder.add_symbol("C", partial).await.unwrap_err();
+        assert_eq!(err.to_string(), "read failed");
+        builder
+            .builder_mut()
+            .add_absolute_symbol("COUNT", 2, None)?;
+        builder.close_verified().await
+    })?;
+
+    assert_eq!(sync, async_);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_builder_cancelled_add_le32() -> Result<()> {
+    use std::future::Future;
+    use tokio::io::AsyncWriteExt;
+
+    let hdr = || Header {
+        class: Class::ELF32,
+        encoding: Encoding::LSB,
+        machine: 0x28,     // ARM instruction set
+        flags: 0x05000000, // ARM ABI version 5
+    };
+
+    let mut builder = Builder::new(hdr(), Vec::new())?;
+    builder.add_symbol("y", &b"why"[..])?;
+    let sync = builder.close()?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let async_ = runtime.block_on(async {
+        let mut builder = AsyncBuilder::new(hdr(), Vec::new())?;
+        builder.builder_mut().set_section_per_symbol(true);
+        let (mut tx, rx) = tokio::io::duplex(64);
+        tx.write_all(b"partial").await?;
+        {
+            // The first poll reads the partial data and then waits for
+            // more, and dropping the future cancels the add.
+            let add = builder.add_symbol_align("x", 16, rx);
+            tokio::pin!(add);
+            let poll = std::future::poll_fn(|cx| {
+                std::task::Poll::Ready(add.as_mut().poll(cx).is_pending())
+            });
+            assert!(poll.await);
+        }
+        builder.builder_mut().set_section_per_symbol(false);
+        builder.add_symbol("y", &b"why"[..]).await?;
+        builder.close().await
+    })?;
+
+    assert_eq!(sync, async_);
+
+    Ok(())
+}
+
+#[test]
+fn reader_round_trip() -> Result<()> {
+    use std::io::Read;
+
+    for &class in [Class::ELF32, Class::ELF64].iter() {
+        for &encoding in [Encoding::LSB, Encoding::MSB].iter() {
+            let mut builder = Builder::new(
+                Header {
+                    class,
+                    encoding,
+                    machine: 0x28, // ARM instruction set
+                    flags: 0x05000000,
+                },
+                Vec::new(),
+            )?;
+            builder.add_symbol("A", &b"ay"[..])?;
+            builder.add_symbol_with(
+                "B",
+                SymbolOptions {
+                    alignment: Some(16),
+                    binding: Binding::Weak,
+                    visibility: Visibility::Hidden,
+                    ..SymbolOptions::default()
+                },
+                &b"bee"[..],
+            )?;
+            builder.add_zeroed_symbol("buf", 5, 4)?;
+            builder.add_absolute_symbol("COUNT", 2, None)?;
+            let buf = builder.close()?;
+
+            let reader = Reader::new(&buf)?;
+            assert_eq!(
+                reader.header(),
+                &Header {
+                    class,
+                    encoding,
+                    machine: 0x28,
+                    flags: 0x05000000,
+                }
+            );
+            assert_eq!(reader.file_type(), 1, "ET_REL");
+            let sections: Vec<(&str, u64, u64)> = reader
+                .sections()
+                .iter()
+                .map(|sect| (&sect.name[..], sect.size, sect.alignment))
+                .collect();
+            assert_eq!(
+                sections,
+                vec![
+                    ("", 0, 0),
+                    (".shstrtab", 40, 0),
+                    (".rodata", 19, 16),
+                    (".bss", 5, 4),
+                    (".strtab", 15, 0),
+                    (".symtab", reader.sections()[5].entsize * 5, 0),
+                ]
+            );
+            let symbols: Vec<(&str, u64, u64, SymbolSection, Binding, Visibility, u64)> = reader
+                .symbols()
+                .iter()
+                .map(|sym| {
+                    (
+                        &sym.name[..],
+                        sym.value,
+                        sym.size,
+                        sym.section,
+                        sym.binding,
+                        sym.visibility,
+                        sym.alignment,
+                    )
+                })
+                .collect();
+            assert_eq!(
+                symbols,
+                vec![
+                    (
+                        "A",
+                        0,
+                        2,
+                        SymbolSection::Index(2),
+                        Binding::Global,
+                        Visibility::Default,
+                        16
+                    ),
+                    (
+                        "B",
+                        16,
+                        3,
+                        SymbolSection::Index(2),
+                        Binding::Weak,
+                        Visibility::Hidden,
+                        16
+                    ),
+                    (
+                        "buf",
+                        0,
+                        5,
+                        SymbolSection::Index(3),
+                        Binding::Global,
+                        Visibility::Default,
+                        4
+                    ),
+                    (
+                        "COUNT",
+                        2,
+                        0,
+                        SymbolSection::Absolute,
+                        Binding::Global,
+                        Visibility::Default,
+                        1
+                    ),
+                ]
+            );
+
+            let mut data = Vec::new();
+            reader
+                .symbol_reader(reader.symbol("B").unwrap())?
+                .read_to_end(&mut data)?;
+            assert_eq!(data, b"bee");
+            data.clear();
+            reader
+                .symbol_reader(reader.symbol("buf").unwrap())?
+                .read_to_end(&mut data)?;
+            assert_eq!(data, [0, 0, 0, 0, 0]);
+            assert!(reader
+                .symbol_reader(reader.symbol("COUNT").unwrap())
+                .is_err());
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn reader_invalid() {
+    let err = Reader::new(b"not an ELF file").err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let buf = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )
+    .unwrap()
+    .close()
+    .unwrap();
+    assert!(Reader::new(&buf).is_ok());
+    let err = Reader::new(&buf[..buf.len() - 1]).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn reader_linked_executable() -> Result<()> {
+    use std::convert::TryInto;
+    use std::io::Read;
+
+    fn contents(reader: &Reader, sym: &reader::SymbolInfo) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        reader.symbol_reader(sym)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    // See testdata/README.md for how this was built.
+    let linked = include_bytes!("../testdata/linked-x86_64.elf");
+    let reader = Reader::new(linked)?;
+    assert_eq!(reader.file_type(), 2, "ET_EXEC");
+
+    let unique = reader.symbol("_ZN7CounterIiE5countE").unwrap();
+    assert_eq!(unique.binding, Binding::Other(10), "STB_GNU_UNIQUE");
+    assert_eq!(contents(&reader, unique)?, vec![1, 0, 0, 0]);
+
+    let tables: Vec<&reader::SymbolInfo> = reader
+        .symbols()
+        .iter()
+        .filter(|sym| sym.name == "_ZL5table")
+        .collect();
+    assert_eq!(tables.len(), 2);
+    assert_eq!(contents(&reader, tables[0])?, vec![1, 2, 3, 4]);
+    assert_eq!(contents(&reader, tables[1])?, vec![5, 6, 7, 8]);
+
+    // Patch the first table's section index to a reserved value, and then
+    // to SHN_XINDEX with its real index in an extended index table, which
+    // we make from the .comment section.
+    let SymbolSection::Index(rodata_idx) = tables[0].section else {
+        panic!("table should be in a section");
+    };
+    let symtab = reader.section(".symtab").unwrap();
+    let comment = reader.section(".comment").unwrap();
+    let table_idx = reader
+        .symbols()
+        .iter()
+        .position(|sym| sym.name == "_ZL5table")
+        .unwrap() as u64
+        + 1;
+    let shndx_at = (symtab.offset + table_idx * 24 + 6) as usize;
+    let shoff = u64::from_le_bytes(linked[40..48].try_into().unwrap()) as usize;
+    let comment_hdr = shoff + comment.index * 64;
+    let entry_at = (comment.offset + table_idx * 4) as usize;
+
+    let mut patched = linked.to_vec();
+    patched[shndx_at..shndx_at + 2].copy_from_slice(&0xff00_u16.to_le_bytes());
+    let table = Reader::new(&patched)?.symbol("_ZL5table").cloned().unwrap();
+    assert_eq!(table.section, SymbolSection::Reserved(0xff00));
+
+    patched[shndx_at..shndx_at + 2].copy_from_slice(&0xffff_u16.to_le_bytes());
+    let err = Reader::new(&patched).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    patched[comment_hdr + 4..comment_hdr + 8].copy_from_slice(&18_u32.to_le_bytes()); // SHT_SYMTAB_SHNDX
+    patched[comment_hdr + 40..comment_hdr + 44]
+        .copy_from_slice(&(symtab.index as u32).to_le_bytes());
+    patched[entry_at..entry_at + 4].copy_from_slice(&(rodata_idx as u32).to_le_bytes());
+    let reader = Reader::new(&patched)?;
+    let table = reader.symbol("_ZL5table").unwrap();
+    assert_eq!(table.section, SymbolSection::Index(rodata_idx));
+    assert_eq!(contents(&reader, table)?, vec![1, 2, 3, 4]);
+
+    Ok(())
+}
+
+#[test]
+fn reader_linked_tls() -> Result<()> {
+    use std::io::Read;
+
+    fn contents(reader: &Reader, name: &str) -> Result<Vec<u8>> {
+        let sym = reader.symbol(name).unwrap();
+        let mut data = Vec::new();
+        reader.symbol_reader(sym)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    // See testdata/README.md for how this was built.
+    let linked = include_bytes!("../testdata/linked-tls-x86_64.elf");
+    let reader = Reader::new(linked)?;
+    assert_eq!(reader.symbol("first_tls").unwrap().typ, 6, "STT_TLS");
+    assert_eq!(contents(&reader, "first_tls")?, vec![1, 2, 3, 4]);
+    assert_eq!(contents(&reader, "second_tls")?, vec![5, 6, 7, 8]);
+    assert_eq!(contents(&reader, "scratch")?, vec![0; 16]);
+    assert_eq!(contents(&reader, "plain")?, vec![9, 10, 11, 12]);
+
+    let scratch = reader.symbol("scratch").unwrap();
+    let tbss = reader.section(".tbss").unwrap();
+    assert_eq!(scratch.section, SymbolSection::Index(tbss.index));
+    assert_eq!(scratch.alignment, tbss.alignment.max(1));
+    Ok(())
+}
+
+/// Builds a file using every kind of section and symbol that [`Builder`]
+/// supports, for checking with [`Builder::close_verified`].
+fn builder_for_verify(class: Class, encoding: Encoding) -> Result<Builder<Vec<u8>>> {
+    let mut builder = Builder::new(
+        Header {
+            class,
+            encoding,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    let logo = builder.add_symbol("logo", &b"logo"[..])?;
+    builder.add_symbol_align("font", 64, &b"font"[..])?;
+    builder.add_symbol_with(
+        "scratch",
+        SymbolOptions {
+            binding: Binding::Local,
+            ..SymbolOptions::default()
+        },
+        &b"abc"[..],
+    )?;
+    builder.add_symbol_alias(logo, "default_logo")?;
+    builder.add_zeroed_symbol("buffer", 100, 16)?;
+    builder.add_string_symbol("greeting", "hello")?;
+    builder.add_absolute_symbol("COUNT", 3, None)?;
+    let assets = builder.add_section(".assets");
+    builder.set_companion_symbols(Some(CompanionNaming::objcopy()))?;
+    builder.add_symbol_align_in(assets, "music", 32, &b"music"[..])?;
+    builder.set_companion_symbols(None)?;
+    let mut table = RelocatableData::new();
+    table.pointer("logo", 0).pointer("external", 4).word(2);
+    builder.add_relocatable_symbol("table", &table)?;
+    Ok(builder)
+}
+
+#[test]
+fn close_verified_all_variants() -> Result<()> {
+    for &class in [Class::ELF32, Class::ELF64].iter() {
+        for &encoding in [Encoding::LSB, Encoding::MSB].iter() {
+            let verified = builder_for_verify(class, encoding)?.close_verified()?;
+            let unverified = builder_for_verify(class, encoding)?.close()?;
+            assert_eq!(verified, unverified, "{:?} {:?}", class, encoding);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_detects_corruption() -> Result<()> {
+    let builder = builder_for_verify(Class::ELF64, Encoding::LSB)?;
+    let good = builder.file_bytes()?;
+    builder.verify(&good)?;
+
+    // Each corruption sets the byte at an offset to a new value.
+    let symtab_offset = Reader::new(&good)?.section(".symtab").unwrap().offset as usize;
+    let corruptions = [
+        ("e_ehsize wrong", 52, 52),
+        ("e_shnum too small", 60, good[60] - 1),
+        ("e_shstrndx wrong", 62, 2),
+        ("symbol outside its section", symtab_offset + 24 + 9, 0x10),
+    ];
+    for &(description, offset, value) in corruptions.iter() {
+        let mut bad = good.clone();
+        bad[offset] = value;
+        let err = builder.verify(&bad).expect_err(description);
+        assert_eq!(
+            err.kind(),
+            std::io::ErrorKind::InvalidData,
+            "{}",
+            description
+        );
+    }
+    let err = builder.verify(&good[..good.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[test]
+fn c_header_le64() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.add_symbol_align("logo", 16, &b"logo"[..])?;
+    builder.add_symbol_with(
+        "scratch",
+        SymbolOptions {
+            binding: Binding::Local,
+            ..SymbolOptions::default()
+        },
+        &b"abc"[..],
+    )?;
+    builder.add_string_symbol("greeting", "hello")?;
+    builder.add_zeroed_symbol("buffer", 100, 1)?;
+    builder.add_absolute_symbol("COUNT", 3, None)?;
+    builder.set_companion_symbols(Some(CompanionNaming::objcopy()))?;
+    builder.add_symbol("font", &b"font"[..])?;
+
+    let header = builder.c_header("DATA_H")?;
+    assert_eq!(
+        header,
+        r#"/* Generated by elfbin. Do not edit. */
+
+#ifndef DATA_H
+#define DATA_H
+
+#include <stdint.h>
+
+#ifndef ELFBIN_ALIGNED
+#if defined(__GNUC__)
+#define ELFBIN_ALIGNED(n) __attribute__((aligned(n)))
+#else
+#define ELFBIN_ALIGNED(n)
+#endif
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define LOGO_SIZE 4
+extern const uint8_t logo[] ELFBIN_ALIGNED(16);
+
+#define GREETING_SIZE 6
+extern const char greeting[];
+
+#define BUFFER_SIZE 100
+extern uint8_t buffer[];
+
+/* Absolute symbol: its address, (uintptr_t)COUNT, is 0x3. */
+extern const uint8_t COUNT[];
+
+#define BINARY_FONT_START_SIZE 4
+extern const uint8_t _binary_font_start[] ELFBIN_ALIGNED(8);
+
+extern const uint8_t _binary_font_end[];
+
+/* Absolute symbol: its address, (uintptr_t)_binary_font_size, is 0x4. */
+extern const uint8_t _binary_font_size[];
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DATA_H */
+"#
+    );
+
+    let err = builder.c_header("data.h").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.set_companion_symbols(None)?;
+    builder.add_symbol("LOGO_SIZE", &b""[..])?;
+    let err = builder.c_header("DATA_H").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.add_symbol("logo", &b"logo"[..])?;
+    builder.c_header("DATA_H")?;
+    for guard in ["int", "_DATA_H", "__data_h"].iter() {
+        let err = builder.c_header(guard).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "{}", guard);
+    }
+    builder.add_symbol("int", &b"int"[..])?;
+    let err = builder.c_header("DATA_H").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+fn cpp_header_le64() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.add_symbol_align("logo", 16, &b"logo"[..])?;
+    builder.add_string_symbol("greeting", "hello")?;
+    builder.add_zeroed_symbol("buffer", 100, 1)?;
+    builder.add_absolute_symbol("COUNT", 3, None)?;
+    builder.set_companion_symbols(Some(CompanionNaming {
+        start: String::from("{}"),
+        end: String::from("{}_end"),
+        size: String::from("{}_size"),
+    }))?;
+    builder.add_symbol("font", &b"font"[..])?;
+
+    let header = builder.cpp_header("DATA_HPP", "assets::images")?;
+    assert_eq!(
+        header,
+        r#"// Generated by elfbin. Do not edit.
+
+#ifndef DATA_HPP
+#define DATA_HPP
+
+#include <cstddef>
+#include <cstdint>
+#include <span>
+#include <string_view>
+
+#ifndef ELFBIN_ALIGNED
+#if defined(__GNUC__)
+#define ELFBIN_ALIGNED(n) __attribute__((aligned(n)))
+#else
+#define ELFBIN_ALIGNED(n)
+#endif
+#endif
+
+extern "C" {
+extern const std::byte logo[] ELFBIN_ALIGNED(16);
+extern const char greeting[];
+extern std::byte buffer[];
+extern const std::byte COUNT[];
+extern const std::byte font[] ELFBIN_ALIGNED(8);
+extern const std::byte font_end[];
+extern const std::byte font_size[];
+}
+
+namespace assets::images {
+
+constexpr std::span<const std::byte, 4> logo() noexcept {
+    return std::span<const std::byte, 4>(::logo, 4);
+}
+
+constexpr std::string_view greeting() noexcept {
+    return std::string_view(::greeting, 5);
+}
+
+constexpr std::span<std::byte, 100> buffer() noexcept {
+    return std::span<std::byte, 100>(::buffer, 100);
+}
+
+inline std::uintptr_t COUNT() noexcept {
+    return reinterpret_cast<std::uintptr_t>(::COUNT);
+}
+
+constexpr std::span<const std::byte, 4> font() noexcept {
+    return std::span<const std::byte, 4>(::font, 4);
+}
+
+constexpr const std::byte *font_end() noexcept {
+    return ::font_end;
+}
+
+inline std::uintptr_t font_size() noexcept {
+    return reinterpret_cast<std::uintptr_t>(::font_size);
+}
+
+} // namespace assets::images
+
+#endif // DATA_HPP
+"#
+    );
+
+    let err = builder.cpp_header("DATA_HPP", "").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = builder.cpp_header("DATA_HPP", "assets::2d").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    for namespace in ["new", "assets::class", "restrict"].iter() {
+        let err = builder.cpp_header("DATA_HPP", namespace).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            std::io::ErrorKind::InvalidInput,
+            "{}",
+            namespace
+        );
+    }
+    let err = builder.cpp_header("_DATA_HPP", "assets").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.add_symbol("template", &b"template"[..])?;
+    let err = builder.cpp_header("DATA_HPP", "assets").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Symbols can't have the same names as the namespaces the header uses.
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.add_symbol("assets", &b"assets"[..])?;
+    builder.cpp_header("DATA_HPP", "game::assets")?;
+    let err = builder.cpp_header("DATA_HPP", "assets").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = builder
+        .cpp_header("DATA_HPP", "assets::images")
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    builder.add_symbol("std", &b"std"[..])?;
+    let err = builder.cpp_header("DATA_HPP", "game").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+fn cpp_names_le32() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF32,
+            encoding: Encoding::LSB,
+            machine: 3, // x86
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.set_cpp_names(Some(CppType::UnsignedShort));
+    builder.add_symbol("assets::images::logo", &b"logo"[..])?;
+    builder.add_symbol("::top", &b"ab"[..])?;
+    builder.add_symbol("std::x", &b"xx"[..])?;
+    builder.add_symbol("std::io::y", &b"yy"[..])?;
+    let mut table = RelocatableData::new();
+    table.pointer("assets::images::logo", 0);
+    builder.add_relocatable_symbol("assets::table", &table)?;
+
+    let err = builder.add_symbol("assets::", &b"ab"[..]).unwrap_err();
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<SymbolNameError>(),
+        Some(&SymbolNameError::NotCppName("assets::".into()))
+    );
+    let err = builder.add_symbol("a::1b", &b"ab"[..]).unwrap_err();
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<SymbolNameError>(),
+        Some(&SymbolNameError::NotCppName("a::1b".into()))
+    );
+    let err = builder
+        .add_symbol("::assets::images::logo", &b"ab"[..])
+        .unwrap_err();
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<SymbolNameError>(),
+        Some(&SymbolNameError::Duplicate(
+            "_ZN6assets6images4logoE".into()
+        ))
+    );
+    let err = builder.add_symbol("odd", &b"abc"[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = builder.add_zeroed_symbol("odd", 5, 2).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    builder.set_cpp_names(Some(CppType::Double));
+    builder.set_section_per_symbol(true);
+    let samples = builder.add_zeroed_symbol_with("ns::samples", 16, SymbolOptions::default())?;
+    assert_eq!(samples.alignment, 8, "aligned to the element type");
+
+    // The declarations in generated source files would use the mangled
+    // names, which aren't what the caller asked for.
+    for result in [
+        builder.c_header("DATA_H"),
+        builder.cpp_header("DATA_HPP", "assets"),
+        builder.rust_module(),
+    ] {
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    let buf = builder.close_verified()?;
+    let reader = Reader::new(&buf)?;
+    let rodata = reader.section(".rodata").unwrap();
+    assert_eq!(
+        reader.section_data(rodata).unwrap(),
+        &b"logoab  xx  yy  \0\0\0\0"[..],
+        "rejected symbols wrote no data"
+    );
+    assert!(reader.section(".bss._ZN2ns7samplesE").is_some());
+    let names: Vec<&str> = reader.symbols().iter().map(|sym| &sym.name[..]).collect();
+    assert_eq!(
+        names,
+        vec![
+            "_ZN6assets6images4logoE",
+            "top",
+            "_ZSt1x",
+            "_ZNSt2io1yE",
+            "_ZN6assets5tableE",
+            "_ZN2ns7samplesE",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn rust_module_le64() -> Result<()> {
+    let mut builder = Builder::new(
+        Header {
+            class: Class::ELF64,
+            encoding: Encoding::LSB,
+            machine: 62, // x86_64
+            flags: 0,
+        },
+        Vec::new(),
+    )?;
+    builder.add_symbol_align("logo", 16, &b"logo"[..])?;
+    builder.add_string_symbol("greeting", "hello")?;
+    builder.add_zeroed_symbol("buffer", 100, 1)?;
+    builder.add_absolute_symbol("COUNT", 3, None)?;
+    builder.set_companion_symbols(Some(CompanionNaming {
+        start: String::from("{}"),
+        end: String::from("{}_end"),
+        size: String::from("{}_size"),
+    }))?;
+    builder.add_symbol("font", &b"font"[..])?;
+
+    let module = builder.rust_module()?;
+    assert_eq!(
+        module,
+        r#"// Generated by elfbin. Do not edit.
+
+mod ffi {
+    #![allow(non_upper_case_globals)]
+
+    unsafe extern "C" {
+        pub static logo: [u8; 4];
+        pub static greeting: [u8; 6];
+        pub static mut buffer: [u8; 100];
+        pub static COUNT: [u8; 0];
+        pub static font: [u8; 4];
+        pub static font_end: [u8; 0];
+        pub static font_size: [u8; 0];
+    }
+}
+
+/// Returns the data of the `logo` symbol.
+pub fn logo() -> &'static [u8] {
+    unsafe { &ffi::logo }
+}
+
+/// Returns the string in the `greeting` symbol.
+pub fn greeting() -> &'static str {
+    unsafe { ::core::str::from_utf8_unchecked(&ffi::greeting[..5]) }
+}
+
+/// Returns a pointer to the data of the `buffer` symbol.
+pub fn buffer() -> *mut [u8; 100] {
+    ::core::ptr::addr_of_mut!(ffi::buffer)
+}
+
+/// Returns the value of the absolute symbol `COUNT`, which is 0x3.
+#[allow(non_snake_case)]
+pub fn COUNT() -> usize {
+    ::core::ptr::addr_of!(ffi::COUNT) as usize
+}
+
+/// Returns the data of the `font` symbol.
+pub fn font() -> &'static [u8] {
+    unsafe { &ffi::font }
+}
+
+/// Returns the address of the `font_end` symbol.
+pub fn font_end() -> *const u8 {
+    ::core::ptr::addr_of!(ffi::font_end) as *const u8
+}
+
+/// Returns the value of the absolute symbol `font_size`, which is 0x4.
+pub fn font_size() -> usize {
+    ::core::ptr::addr_of!(ffi::font_size) as usize
+}
+"#
+    );
+
+    builder.add_symbol("type", &b"type"[..])?;
+    let err = builder.rust_module().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+/// A reader that produces the given bytes and then fails.
+struct FailingReader<'a>(&'a [u8]);
+
+impl std::io::Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.0.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "read failed",
+            ));
+        }
+        let n = self.0.read(buf)?;
+        Ok(n)
+    }
+}
+
+/// An async reader whose reads always fail.
+#[cfg(feature = "tokio")]
+struct FailingAsyncReader;
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for FailingAsyncReader {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        _buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<Result<()>> {
+        std::task::Poll::Ready(Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "read failed",
+        )))
+    }
+}
@@ -0,0 +1,349 @@
+//! Checking the files produced by a [`Builder`], for
+//! [`Builder::close_verified`].
+//!
+//! The checks parse the file independently of the code that generated it,
+//! using [`Reader`] along with some lower-level reads of fields that the
+//! reader doesn't expose, and compare the results with what the builder
+//! was asked to produce.
+
+use crate::io::{Error, ErrorKind, Result, Write};
+use crate::reader::{Decoder, SectionInfo, SymbolSection};
+use crate::{Binding, Builder, Class, Reader, Section};
+use crate::{ET_REL, FIRST_DATA_SECTION_IDX, SHF_INFO_LINK, SHN_ABS, SHN_UNDEF};
+use crate::{SHT_NULL, SHT_REL, SHT_RELA, SHT_STRTAB, SHT_SYMTAB};
+use alloc::format;
+use alloc::vec::Vec;
+
+impl<W: Write> Builder<W> {
+    /// Checks that the given file, which must have been generated from this
+    /// builder, is well-formed and matches the builder's contents.
+    pub(crate) fn verify(&self, data: &[u8]) -> Result<()> {
+        let reader = Reader::new(data).map_err(failed)?;
+        let hdr = self.header();
+        if *reader.header() != hdr {
+            return Err(failed("ELF header doesn't match the builder"));
+        }
+        if reader.file_type() != ET_REL {
+            return Err(failed("file type isn't ET_REL"));
+        }
+
+        let d = Decoder {
+            data,
+            class: hdr.class,
+            encoding: hdr.encoding,
+        };
+        let (ehsize, shoff, shentsize, shnum, shstrndx) = match hdr.class {
+            Class::ELF32 => (d.u16(40)?, d.word(32)?, d.u16(46)?, d.u16(48)?, d.u16(50)?),
+            Class::ELF64 => (d.u16(52)?, d.word(40)?, d.u16(58)?, d.u16(60)?, d.u16(62)?),
+        };
+        let (expected_ehsize, word_size, symbol_entsize) = match hdr.class {
+            Class::ELF32 => (52, 4, 16),
+            Class::ELF64 => (64, 8, 24),
+        };
+        if data[6] != 1 || d.u32(20)? != 1 {
+            return Err(failed("ELF version isn't 1"));
+        }
+        if ehsize != expected_ehsize {
+            return Err(failed(format!("e_ehsize is {}", ehsize)));
+        }
+
+        // The section header table must be after the ELF header, and the
+        // reader has already checked that it's within the file.
+        let table_size = shnum as u64 * shentsize as u64;
+        if shoff < ehsize as u64 || shoff % word_size != 0 {
+            return Err(failed(format!(
+                "section header table is at invalid offset {:#x}",
+                shoff
+            )));
+        }
+        let reloc_sections: Vec<Section> = (0..self.sections.len())
+            .map(Section)
+            .filter(|&section| self.relocations.iter().any(|r| r.section == section))
+            .collect();
+        let strtab_idx = FIRST_DATA_SECTION_IDX as usize + self.sections.len();
+        let symtab_idx = strtab_idx + 1;
+        let expected_shnum = symtab_idx + 1 + reloc_sections.len();
+        let sections = reader.sections();
+        if shnum as usize != expected_shnum || sections.len() != expected_shnum {
+            return Err(failed(format!(
+                "e_shnum is {}, but there should be {} sections",
+                shnum, expected_shnum
+            )));
+        }
+
+        match sections.get(shstrndx as usize) {
+            Some(sect) if sect.typ == SHT_STRTAB && sect.name == ".shstrtab" => (),
+            _ => {
+                return Err(failed(format!(
+                    "e_shstrndx {} doesn't refer to .shstrtab",
+                    shstrndx
+                )))
+            }
+        }
+
+        let null = &sections[0];
+        if null.typ != SHT_NULL || null.offset != 0 || null.size != 0 || null.link != 0 {
+            return Err(failed("section zero isn't a null section"));
+        }
+        for sect in sections[1..].iter() {
+            verify_section_placement(&reader, sect, ehsize as u64, shoff, table_size)?;
+        }
+
+        for (i, expected) in self.sections.iter().enumerate() {
+            let sect = &sections[Section(i).header_idx() as usize];
+            if sect.name != expected.name()
+                || sect.typ != expected.typ()
+                || sect.flags != expected.flags() as u64
+                || sect.size != expected.contents.len()
+                || sect.alignment != expected.alignment as u64
+                || sect.entsize != expected.entsize()
+            {
+                return Err(failed(format!(
+                    "section {} doesn't match the builder",
+                    sect.name
+                )));
+            }
+        }
+
+        let strtab = &sections[strtab_idx];
+        let symtab = &sections[symtab_idx];
+        if strtab.typ != SHT_STRTAB || strtab.name != ".strtab" {
+            return Err(failed(format!("section {} isn't .strtab", strtab_idx)));
+        }
+        if symtab.typ != SHT_SYMTAB
+            || symtab.name != ".symtab"
+            || symtab.link as usize != strtab_idx
+            || symtab.entsize != symbol_entsize
+            || symtab.size % symbol_entsize != 0
+        {
+            return Err(failed("invalid .symtab section header"));
+        }
+        if symtab.size > 0
+            && d.range(symtab.offset, symbol_entsize)?
+                .iter()
+                .any(|&b| b != 0)
+        {
+            return Err(failed("symbol zero isn't a null symbol"));
+        }
+        self.verify_symbols(&reader, symtab)?;
+
+        for (i, &section) in reloc_sections.iter().enumerate() {
+            let sect = &sections[symtab_idx + 1 + i];
+            self.verify_relocations(&d, &reader, sect, section, symtab_idx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the symbol table contains exactly the builder's symbols,
+    /// plus undefined symbols for relocation targets, and that each one is
+    /// within its section and suitably aligned.
+    fn verify_symbols(&self, reader: &Reader, symtab: &SectionInfo) -> Result<()> {
+        let symbols = reader.symbols();
+        let local_count = symbols
+            .iter()
+            .take_while(|sym| sym.binding == Binding::Local)
+            .count();
+        if symbols[local_count..]
+            .iter()
+            .any(|sym| sym.binding == Binding::Local)
+        {
+            return Err(failed("local symbols aren't all before global symbols"));
+        }
+        if symtab.info as usize != local_count + 1 {
+            return Err(failed(format!(
+                ".symtab sh_info is {}, but should be {}",
+                symtab.info,
+                local_count + 1
+            )));
+        }
+
+        for sym in symbols.iter() {
+            let entry = self.symbols.iter().find(|entry| entry.name == sym.name);
+            let entry = match entry {
+                Some(entry) => entry,
+                None if sym.section == SymbolSection::Undefined
+                    && self.relocations.iter().any(|r| r.target == sym.name) =>
+                {
+                    continue
+                }
+                None => {
+                    return Err(failed(format!("unexpected symbol {:?}", sym.name)));
+                }
+            };
+            let section = match entry.section_idx {
+                SHN_UNDEF => SymbolSection::Undefined,
+                SHN_ABS => SymbolSection::Absolute,
+                idx => SymbolSection::Index(idx as usize),
+            };
+            if sym.section != section
+                || sym.value != entry.value
+                || sym.size != entry.size
+                || sym.typ != entry.typ
+                || sym.binding != entry.binding
+                || sym.visibility != entry.visibility
+            {
+                return Err(failed(format!(
+                    "symbol {:?} doesn't match the builder",
+                    sym.name
+                )));
+            }
+            if let SymbolSection::Index(idx) = sym.section {
+                let sect = &reader.sections()[idx];
+                let first = FIRST_DATA_SECTION_IDX as usize;
+                if idx < first || idx >= first + self.sections.len() {
+                    return Err(failed(format!(
+                        "symbol {:?} isn't in a data section",
+                        sym.name
+                    )));
+                }
+                if sym.value > sect.size || sym.size > sect.size - sym.value {
+                    return Err(failed(format!(
+                        "symbol {:?} extends beyond the end of section {}",
+                        sym.name, sect.name
+                    )));
+                }
+                if sym.value % entry.alignment != 0 || sect.alignment.max(1) < entry.alignment {
+                    return Err(failed(format!(
+                        "symbol {:?} isn't aligned to {}",
+                        sym.name, entry.alignment
+                    )));
+                }
+            }
+        }
+
+        let defined = symbols
+            .iter()
+            .filter(|sym| self.symbols.iter().any(|entry| entry.name == sym.name))
+            .count();
+        if defined != self.symbols.len() {
+            return Err(failed(format!(
+                "symbol table has {} of the builder's {} symbols",
+                defined,
+                self.symbols.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that a relocation section contains exactly the builder's
+    /// relocations for the given data section.
+    fn verify_relocations(
+        &self,
+        d: &Decoder,
+        reader: &Reader,
+        sect: &SectionInfo,
+        section: Section,
+        symtab_idx: usize,
+    ) -> Result<()> {
+        let expected: Vec<_> = self
+            .relocations
+            .iter()
+            .filter(|r| r.section == section)
+            .collect();
+        let kind = expected[0].kind;
+        let target = &reader.sections()[section.header_idx() as usize];
+        let (typ, entsize) = match (kind.rela, d.class) {
+            (false, Class::ELF32) => (SHT_REL, 8),
+            (false, Class::ELF64) => (SHT_REL, 16),
+            (true, Class::ELF32) => (SHT_RELA, 12),
+            (true, Class::ELF64) => (SHT_RELA, 24),
+        };
+        let name = format!("{}{}", kind.section_prefix(), target.name);
+        if sect.name != name
+            || sect.typ != typ
+            || sect.flags != SHF_INFO_LINK as u64
+            || sect.link as usize != symtab_idx
+            || sect.info as usize != target.index
+            || sect.entsize != entsize
+            || sect.size != entsize * expected.len() as u64
+        {
+            return Err(failed(format!("invalid {} section header", name)));
+        }
+
+        let word_size = match d.class {
+            Class::ELF32 => 4,
+            Class::ELF64 => 8,
+        };
+        for (i, reloc) in expected.iter().enumerate() {
+            let pos = sect.offset + i as u64 * entsize;
+            let offset = d.word(pos)?;
+            let info = d.word(pos + word_size)?;
+            let (sym_idx, rel_typ) = match d.class {
+                Class::ELF32 => (info >> 8, info & 0xff),
+                Class::ELF64 => (info >> 32, info & 0xffff_ffff),
+            };
+            let addend = match (kind.rela, d.class) {
+                (false, _) => reloc.addend,
+                (true, Class::ELF32) => d.word(pos + 8)? as u32 as i32 as i64,
+                (true, Class::ELF64) => d.word(pos + 16)? as i64,
+            };
+            let sym = match sym_idx as usize {
+                0 => None,
+                idx => reader.symbols().get(idx - 1),
+            };
+            if offset != reloc.offset
+                || offset > target.size
+                || word_size > target.size - offset
+                || rel_typ != kind.typ as u64
+                || sym.map(|sym| &sym.name) != Some(&reloc.target)
+                || addend != reloc.addend
+            {
+                return Err(failed(format!("relocation {} in {} is invalid", i, name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the contents of a section are within the file, don't
+/// overlap the headers, and honor the section's alignment, and that
+/// string tables are null-terminated.
+fn verify_section_placement(
+    reader: &Reader,
+    sect: &SectionInfo,
+    ehsize: u64,
+    shoff: u64,
+    table_size: u64,
+) -> Result<()> {
+    if sect.alignment > 1 && !sect.alignment.is_power_of_two() {
+        return Err(failed(format!(
+            "section {} has invalid alignment {}",
+            sect.name, sect.alignment
+        )));
+    }
+    if sect.is_zeroed() {
+        return Ok(());
+    }
+    let end = sect.offset + sect.size;
+    if sect.size > 0 && (sect.offset < ehsize || (sect.offset < shoff + table_size && end > shoff))
+    {
+        return Err(failed(format!(
+            "section {} overlaps the ELF headers",
+            sect.name
+        )));
+    }
+    if sect.alignment > 1 && sect.offset % sect.alignment != 0 {
+        return Err(failed(format!(
+            "section {} is at offset {:#x}, which isn't aligned to {}",
+            sect.name, sect.offset, sect.alignment
+        )));
+    }
+    if sect.typ == SHT_STRTAB {
+        let table = reader.section_data(sect).unwrap_or(&[]);
+        if table.first() != Some(&0) || table.last() != Some(&0) {
+            return Err(failed(format!(
+                "string table {} isn't null-terminated at both ends",
+                sect.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn failed(message: impl core::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("output verification failed: {}", message),
+    )
+}
@@ -0,0 +1,178 @@
+//! Embedded filesystems, where many files are stored under a single symbol
+//! along with a table of contents for finding them by path.
+//!
+//! A [`Filesystem`] is added to an object file using
+//! [`Builder::add_filesystem`](crate::Builder::add_filesystem), which defines
+//! one root symbol whose data has the following layout. All integers are
+//! unsigned 32-bit values in the encoding of the destination ELF file, and
+//! all offsets are relative to the root symbol so that no relocations are
+//! required:
+//!
+//! | Offset        | Contents                                              |
+//! |---------------|-------------------------------------------------------|
+//! | 0             | The four bytes of [`MAGIC`]                           |
+//! | 4             | The number of entries                                 |
+//! | 8             | The entries, each of which is three integers          |
+//! | after entries | The paths, each followed by a NUL terminator          |
+//! | after paths   | The file contents, each aligned to [`FILE_ALIGNMENT`] |
+//!
+//! Each entry consists of the offset of the file's path, the offset of the
+//! file's contents, and the size of the file's contents in bytes. The
+//! entries are sorted by path in bytewise order, so a reader can use a
+//! binary search to find a particular file.
+//!
+//! In C, the structure can be described as follows:
+//!
+//! ```c
+//! struct elfbin_fs_entry {
+//!     uint32_t path_offset;
+//!     uint32_t data_offset;
+//!     uint32_t size;
+//! };
+//!
+//! struct elfbin_fs {
+//!     char magic[4];
+//!     uint32_t count;
+//!     struct elfbin_fs_entry entries[];
+//! };
+//! ```
+
+use crate::io::{Error, ErrorKind, Read, Result};
+use crate::Encoding;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The magic number at the start of every embedded filesystem.
+pub const MAGIC: [u8; 4] = *b"EBFS";
+
+/// The alignment of each file's contents, relative to the start of the
+/// filesystem.
+///
+/// The filesystem's root symbol is always aligned to at least this value,
+/// so the file contents are also aligned to it in memory.
+pub const FILE_ALIGNMENT: usize = 8;
+
+const HEADER_SIZE: usize = 8;
+const ENTRY_SIZE: usize = 12;
+
+/// A set of files to embed together under a single symbol.
+#[derive(Clone, Debug, Default)]
+pub struct Filesystem {
+    files: Vec<File>,
+}
+
+#[derive(Clone, Debug)]
+struct File {
+    path: String,
+    data: Vec<u8>,
+}
+
+impl Filesystem {
+    /// Returns an empty filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given path, taking its contents from any
+    /// [`Read`] implementation.
+    ///
+    /// Paths are arbitrary strings, and are stored exactly as given. It's an
+    /// error to add a path that is empty, contains a NUL character, or was
+    /// already added.
+    pub fn add_file<R: Read>(&mut self, path: impl Into<String>, mut src: R) -> Result<()> {
+        let path = path.into();
+        if path.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "file path must not be empty",
+            ));
+        }
+        if path.contains('\0') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("file path {:?} contains a NUL character", path),
+            ));
+        }
+        if self.files.iter().any(|file| file.path == path) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("duplicate file path {:?}", path),
+            ));
+        }
+        let mut data = Vec::new();
+        src.read_to_end(&mut data)?;
+        self.files.push(File { path, data });
+        Ok(())
+    }
+
+    /// Returns the number of files in the filesystem.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true if the filesystem has no files.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the encoded filesystem, including the table of contents and
+    /// the contents of all of the files.
+    ///
+    /// Returns an error if the result would be too large to describe using
+    /// 32-bit offsets.
+    pub fn encode(&self, encoding: Encoding) -> Result<Vec<u8>> {
+        let mut files: Vec<&File> = self.files.iter().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut paths: Vec<u8> = Vec::new();
+        let mut path_offsets: Vec<usize> = Vec::with_capacity(files.len());
+        let paths_start = HEADER_SIZE + ENTRY_SIZE * files.len();
+        for file in files.iter() {
+            path_offsets.push(paths_start + paths.len());
+            paths.extend_from_slice(file.path.as_bytes());
+            paths.push(0); // null terminator
+        }
+
+        let mut contents: Vec<u8> = Vec::new();
+        let mut data_offsets: Vec<usize> = Vec::with_capacity(files.len());
+        let contents_start = align_up(paths_start + paths.len());
+        for file in files.iter() {
+            contents.resize(align_up(contents.len()), 0);
+            data_offsets.push(contents_start + contents.len());
+            contents.extend_from_slice(&file.data);
+        }
+
+        let total = contents_start + contents.len();
+        if total > u32::MAX as usize {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "filesystem is too large for 32-bit offsets",
+            ));
+        }
+
+        let mut ret: Vec<u8> = Vec::with_capacity(total);
+        ret.extend_from_slice(&MAGIC);
+        put_u32(&mut ret, encoding, files.len() as u32);
+        for (i, file) in files.iter().enumerate() {
+            put_u32(&mut ret, encoding, path_offsets[i] as u32);
+            put_u32(&mut ret, encoding, data_offsets[i] as u32);
+            put_u32(&mut ret, encoding, file.data.len() as u32);
+        }
+        ret.extend_from_slice(&paths);
+        ret.resize(contents_start, 0);
+        ret.extend_from_slice(&contents);
+        Ok(ret)
+    }
+}
+
+fn align_up(offset: usize) -> usize {
+    offset.next_multiple_of(FILE_ALIGNMENT)
+}
+
+fn put_u32(into: &mut Vec<u8>, encoding: Encoding, v: u32) {
+    match encoding {
+        Encoding::LSB => into.extend_from_slice(&v.to_le_bytes()),
+        Encoding::MSB => into.extend_from_slice(&v.to_be_bytes()),
+    }
+}
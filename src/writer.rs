@@ -0,0 +1,91 @@
+use crate::Encoding;
+use alloc::vec::Vec;
+
+/// Accumulates the bytes of part of an ELF file in memory, encoding integer
+/// fields in a particular byte order.
+pub(crate) struct Writer {
+    buf: Vec<u8>,
+    base: u64,
+    encoding: Encoding,
+}
+
+impl Writer {
+    /// Returns a writer for the bytes at the start of the file.
+    pub(crate) fn new(encoding: Encoding) -> Self {
+        Self::at(encoding, 0)
+    }
+
+    /// Returns a writer for the bytes starting at the given position in the
+    /// file, so that positions and alignment are relative to the file.
+    pub(crate) fn at(encoding: Encoding, base: u64) -> Self {
+        Self {
+            buf: Vec::new(),
+            base,
+            encoding,
+        }
+    }
+
+    /// Appends the given value.
+    pub(crate) fn write<T: Field>(&mut self, v: T) {
+        v.encode(self.encoding, &mut self.buf);
+    }
+
+    /// Overwrites a value previously written at the given position, such as
+    /// a placeholder for a value that wasn't known yet.
+    pub(crate) fn patch<T: Field>(&mut self, pos: u64, v: T) {
+        let mut encoded = Vec::new();
+        v.encode(self.encoding, &mut encoded);
+        let start = (pos - self.base) as usize;
+        self.buf[start..start + encoded.len()].copy_from_slice(&encoded);
+    }
+
+    /// Returns the position in the file of the next byte to be written.
+    pub(crate) fn position(&self) -> u64 {
+        self.base + self.buf.len() as u64
+    }
+
+    /// Appends zero bytes until the position is a multiple of `alignment`.
+    pub(crate) fn align(&mut self, alignment: usize) {
+        let pad_err = (self.position() % alignment as u64) as usize;
+        if pad_err != 0 {
+            self.skip(alignment - pad_err);
+        }
+    }
+
+    /// Appends `n` zero bytes.
+    pub(crate) fn skip(&mut self, n: usize) {
+        self.buf.resize(self.buf.len() + n, 0);
+    }
+
+    pub(crate) fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// A value that [`Writer`] knows how to encode.
+pub(crate) trait Field {
+    fn encode(self, encoding: Encoding, into: &mut Vec<u8>);
+}
+
+macro_rules! int_field {
+    ($($t:ty),*) => {
+        $(
+            impl Field for $t {
+                fn encode(self, encoding: Encoding, into: &mut Vec<u8>) {
+                    match encoding {
+                        Encoding::LSB => into.extend_from_slice(&self.to_le_bytes()),
+                        Encoding::MSB => into.extend_from_slice(&self.to_be_bytes()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_field!(u8, u16, u32, u64);
+
+impl Field for &[u8] {
+    fn encode(self, _: Encoding, into: &mut Vec<u8>) {
+        into.extend_from_slice(self);
+    }
+}
@@ -0,0 +1,6 @@
+# An untyped symbol that has a size, as some assemblers produce.
+	.section .rodata
+	.globl	sized
+sized:
+	.ascii	"sized"
+	.size	sized, 5
@@ -0,0 +1,14 @@
+// Part of a linked executable for the reader tests. Each file defines a
+// local object named table, and the inline variable has the
+// STB_GNU_UNIQUE binding.
+template <typename T> struct Counter {
+    static inline int count = 1;
+};
+
+__attribute__((used)) static const unsigned char table[4] = {1, 2, 3, 4};
+
+int from_b();
+
+extern "C" int _start() {
+    return Counter<int>::count + table[0] + from_b();
+}
@@ -0,0 +1,10 @@
+// Part of a linked executable for the reader tests.
+template <typename T> struct Counter {
+    static inline int count = 1;
+};
+
+__attribute__((used)) static const unsigned char table[4] = {5, 6, 7, 8};
+
+int from_b() {
+    return Counter<int>::count + table[1];
+}
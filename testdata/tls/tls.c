@@ -0,0 +1,12 @@
+/* A linked executable with thread-local variables, for the reader tests.
+ * The values of TLS symbols are offsets into the TLS block rather than
+ * addresses, so first_tls and second_tls are in .tdata and scratch is in
+ * .tbss. */
+__attribute__((used)) _Thread_local unsigned char first_tls[4] = {1, 2, 3, 4};
+__attribute__((used)) _Thread_local unsigned char second_tls[4] = {5, 6, 7, 8};
+__attribute__((used)) _Thread_local unsigned char scratch[16];
+__attribute__((used)) const unsigned char plain[4] = {9, 10, 11, 12};
+
+int _start(void) {
+    return first_tls[0] + second_tls[0] + scratch[0] + plain[0];
+}